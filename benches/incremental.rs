@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use lox::parser::{incremental::reparse, parser::Parser};
+use lox::scanner::incremental::{relex, TextEdit};
+use lox::scanner::scanner::Scanner;
+
+fn ten_thousand_lines() -> String {
+    let mut source = String::new();
+    for i in 0..5_000 {
+        source.push_str(&format!(
+            "var valor_{i} = (1234 + {i}) * 2 >= 10 != !true; // comentario {i}\nprint \"línea ${{valor_{i}}} ✓\";\n"
+        ));
+    }
+    source
+}
+
+fn edit_in_the_middle(c: &mut Criterion) {
+    let source = ten_thousand_lines();
+    let tokens = Scanner::new(source.clone()).scan_tokens().unwrap();
+    let middle = source[source.len() / 2..].find("1234").unwrap() + source.len() / 2;
+    let edit = TextEdit::new(middle..middle + 1, "9");
+
+    let mut group = c.benchmark_group("edit_10k_lines");
+    group.bench_function("full_rescan", |b| {
+        b.iter_batched(
+            || {
+                let mut edited = source.clone();
+                edited.replace_range(edit.range.clone(), &edit.replacement);
+                edited
+            },
+            |edited| Scanner::new(edited).scan_tokens().unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("relex", |b| {
+        b.iter_batched(
+            || (source.clone(), tokens.clone()),
+            |(mut source, mut tokens)| {
+                relex(&mut source, &mut tokens, &edit).unwrap();
+                (source, tokens)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+
+    let program = Parser::new(tokens.clone()).parse().unwrap();
+    let mut group = c.benchmark_group("reparse_10k_lines");
+    group.bench_function("full_parse", |b| {
+        b.iter_batched(
+            || {
+                let mut edited = source.clone();
+                edited.replace_range(edit.range.clone(), &edit.replacement);
+                edited
+            },
+            |edited| {
+                let tokens = Scanner::new(edited).scan_tokens().unwrap();
+                Parser::new(tokens).parse().unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("reparse", |b| {
+        b.iter_batched(
+            || (program.clone(), source.clone(), tokens.clone()),
+            |(program, mut source, mut tokens)| {
+                reparse(program, &mut source, &mut tokens, &edit).unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    // Adds a line, so every statement after the edit has to be moved.
+    let new_line = TextEdit::new(middle..middle, "\n");
+    group.bench_function("reparse_moving_lines", |b| {
+        b.iter_batched(
+            || (program.clone(), source.clone(), tokens.clone()),
+            |(program, mut source, mut tokens)| {
+                reparse(program, &mut source, &mut tokens, &new_line).unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, edit_in_the_middle);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lox::scanner::scanner::Scanner;
+
+// Roughly what a code generator spits out: long runs of declarations and expressions with
+// some non-ASCII strings mixed in so the UTF-8 path gets exercised too.
+fn generated_source(lines: usize) -> String {
+    let mut source = String::new();
+    for i in 0..lines {
+        source.push_str(&format!(
+            "var valor_{i} = (1234 + {i}) * 2 >= 10 != !true;\nprint \"línea número {i} ✓\";\n"
+        ));
+    }
+    source
+}
+
+fn scan_generated(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan_tokens");
+    for lines in [1_000, 10_000, 50_000] {
+        let source = generated_source(lines);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &source, |b, source| {
+            b.iter(|| {
+                let mut scanner = Scanner::new(source.clone());
+                scanner.scan_tokens().unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn scan_streamed(c: &mut Criterion) {
+    let source = generated_source(50_000);
+    let mut group = c.benchmark_group("scan_reader");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("50000", |b| {
+        b.iter(|| {
+            let reader = std::io::Cursor::new(source.clone().into_bytes());
+            Scanner::from_reader(reader).filter(Result::is_ok).count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scan_generated, scan_streamed);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::{error::RuntimeError, function::LoxFunction, value::Value};
+use crate::parser::expressions::Name;
+use crate::scanner::symbol::Symbol;
+
+/// A class: its methods, and the class it inherits the rest from.
+#[derive(Debug)]
+pub struct LoxClass {
+    pub name: Symbol,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    /// The method called `name`, looked up through the superclasses.
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        self.methods.get(&name).cloned().or_else(|| {
+            self.superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name))
+        })
+    }
+
+    /// Calling a class takes the arguments of its `init`.
+    pub fn arity(&self) -> usize {
+        self.find_method(Symbol::intern("init"))
+            .map_or(0, |init| init.arity())
+    }
+}
+
+/// An object made by calling a class. Fields shadow methods of the same name.
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<Symbol, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        LoxInstance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// The field `name` of `instance`, or its method bound to it.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Name) -> Result<Value, RuntimeError> {
+        let this = instance.borrow();
+        if let Some(value) = this.fields.get(&name.symbol) {
+            return Ok(value.clone());
+        }
+        match this.class.find_method(name.symbol) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance.clone())))),
+            None => Err(RuntimeError::new(
+                name.location(),
+                format!("Undefined property '{}'.", name.symbol),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Name, value: Value) {
+        self.fields.insert(name.symbol, value);
+    }
+}
+
+// Fields can refer back to the instance, so they aren't printed.
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LoxInstance({})", self.class.name)
+    }
+}
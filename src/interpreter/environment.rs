@@ -0,0 +1,117 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{error::RuntimeError, value::Value};
+use crate::parser::expressions::Name;
+use crate::scanner::symbol::Symbol;
+
+/// The variables of one scope, linked to the scope around it. A block gets a fresh scope
+/// whose names shadow the enclosing ones until the block ends.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Declares `name` in this scope. Declaring it again replaces the old value.
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    pub fn get(&self, name: &Name) -> Result<Value, RuntimeError> {
+        match self.values.get(&name.symbol) {
+            Some(value) => Ok(value.clone()),
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => Err(undefined(name)),
+            },
+        }
+    }
+
+    /// Sets the innermost variable called `name`, which must already be declared.
+    pub fn assign(&mut self, name: &Name, value: Value) -> Result<(), RuntimeError> {
+        match self.values.get_mut(&name.symbol) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err(undefined(name)),
+            },
+        }
+    }
+}
+
+fn undefined(name: &Name) -> RuntimeError {
+    RuntimeError::new(
+        name.location(),
+        format!("Undefined variable '{}'.", name.symbol),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::expressions::Location;
+
+    fn name(text: &str) -> Name {
+        Name {
+            symbol: Symbol::intern(text),
+            location: Location {
+                line: 3,
+                col: 7,
+                ..Location::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_shadowing_and_assignment() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .define(name("a").symbol, Value::Number(1.0));
+        globals
+            .borrow_mut()
+            .define(name("b").symbol, Value::Number(2.0));
+
+        let mut inner = Environment::with_enclosing(globals.clone());
+        inner.define(name("a").symbol, Value::Bool(true));
+        inner.assign(&name("b"), Value::Nil).unwrap();
+
+        assert_eq!(inner.get(&name("a")).unwrap(), Value::Bool(true));
+        assert_eq!(
+            globals.borrow().get(&name("a")).unwrap(),
+            Value::Number(1.0)
+        );
+        assert_eq!(globals.borrow().get(&name("b")).unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        let mut environment = Environment::new();
+        let error = environment.get(&name("nope")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 3:7] Error: Undefined variable 'nope'."
+        );
+        assert_eq!(
+            environment.assign(&name("nope"), Value::Nil).unwrap_err(),
+            error
+        );
+    }
+}
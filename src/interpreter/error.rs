@@ -0,0 +1,57 @@
+use std::fmt;
+
+use super::value::Value;
+use crate::parser::expressions::Location;
+use crate::scanner::source::{FileId, Span};
+use crate::scanner::symbol::Symbol;
+
+/// An error raised while running a program, at the place in the source that caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub file: FileId,
+    pub span: Span,
+    pub line: usize,
+    pub col: u32,
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(location: Location, message: impl Into<String>) -> Self {
+        RuntimeError {
+            file: location.file,
+            span: location.span,
+            line: location.line,
+            col: location.col,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error: {}",
+            self.line, self.col, self.message
+        )
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Why running a statement stopped early: a runtime error, a `return` carrying its value
+/// out to the call that is returning, or a `break` or `continue` going out to the loop with
+/// its label, or the innermost one if it has none.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+    Break(Option<Symbol>),
+    Continue(Option<Symbol>),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
@@ -0,0 +1,116 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use super::{class::LoxInstance, environment::Environment, map::MapKey, value::Value};
+use crate::parser::statements::FunctionStmt;
+use crate::scanner::symbol::Symbol;
+
+/// A function declared in Lox, together with the scope it was declared in, which its body can
+/// keep using after that scope has ended. An initializer is a class's `init` method, which
+/// always returns the instance.
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionStmt>,
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    /// This method with `this` set to `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define(Symbol::intern("this"), Value::Instance(instance));
+        LoxFunction {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+// The closure can hold this function, so printing it would never end.
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LoxFunction({})", self.declaration.name.symbol)
+    }
+}
+
+/// A function built into the interpreter. An `Err` is a runtime error message.
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Result<Value, String>,
+}
+
+/// Seconds since the Unix epoch, for timing programs.
+pub fn clock(_: &[Value]) -> Result<Value, String> {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| Value::Number(elapsed.as_secs_f64()))
+        .map_err(|e| format!("Could not read the clock: {}.", e))
+}
+
+/// The number of elements in a list, entries in a map, or characters in a string.
+pub fn len(arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::List(list) => Ok(Value::Number(list.borrow().len() as f64)),
+        Value::Map(map) => Ok(Value::Number(map.borrow().len() as f64)),
+        Value::Str(s) => Ok(Value::Number(s.chars().count() as f64)),
+        value => Err(format!("Can't take the length of a {}.", value.type_name())),
+    }
+}
+
+/// Adds a value to the end of a list and returns the list.
+pub fn push(arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::List(list) => {
+            list.borrow_mut().push(arguments[1].clone());
+            Ok(arguments[0].clone())
+        }
+        value => Err(format!(
+            "Can only push onto a list, not a {}.",
+            value.type_name()
+        )),
+    }
+}
+
+/// Whether a map has a key.
+pub fn has(arguments: &[Value]) -> Result<Value, String> {
+    let Value::Map(map) = &arguments[0] else {
+        return Err(format!(
+            "Can only look up keys in a map, not a {}.",
+            arguments[0].type_name()
+        ));
+    };
+    let key = MapKey::try_from(&arguments[1])?;
+    Ok(Value::Bool(map.borrow().contains_key(&key)))
+}
+
+/// A new list of the keys of a map, in the order they were first inserted.
+pub fn keys(arguments: &[Value]) -> Result<Value, String> {
+    let Value::Map(map) = &arguments[0] else {
+        return Err(format!(
+            "Can only list the keys of a map, not a {}.",
+            arguments[0].type_name()
+        ));
+    };
+    let keys = map.borrow().keys().map(Value::from).collect();
+    Ok(Value::List(Rc::new(RefCell::new(keys))))
+}
+
+/// Takes a key out of a map, keeping the order of the rest, and returns its value or `nil`.
+pub fn remove(arguments: &[Value]) -> Result<Value, String> {
+    let Value::Map(map) = &arguments[0] else {
+        return Err(format!(
+            "Can only remove keys from a map, not a {}.",
+            arguments[0].type_name()
+        ));
+    };
+    let key = MapKey::try_from(&arguments[1])?;
+    Ok(map.borrow_mut().shift_remove(&key).unwrap_or(Value::Nil))
+}
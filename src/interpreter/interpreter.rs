@@ -0,0 +1,1286 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+
+use super::{
+    class::{LoxClass, LoxInstance},
+    environment::Environment,
+    error::{RuntimeError, Unwind},
+    function::{self, LoxFunction, NativeFunction},
+    map::{Map, MapKey},
+    value::Value,
+};
+use crate::parser::{
+    expressions::{
+        AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr, IndexSetExpr,
+        InterpolationExpr, LambdaExpr, ListExpr, LiteralExpr, Location, LogicalExpr,
+        LogicalOperator, MapExpr, Name, Operator, SetExpr, SliceExpr, SuperExpr, TernaryExpr,
+        ThisExpr, UnaryExpr, UnaryOperator, VariableExpr, Visitor,
+    },
+    statements::{
+        BlockStmt, BreakStmt, ClassStmt, ContinueStmt, ExpressionStmt, FunctionStmt, IfStmt,
+        PrintStmt, Program, ReturnStmt, Stmt, StmtVisitor, VarStmt, WhileStmt,
+    },
+};
+use crate::scanner::symbol::Symbol;
+
+type Evaluation = Result<Value, RuntimeError>;
+type Execution = Result<(), Unwind>;
+
+/// How many Lox calls may be nested before the program is stopped with a stack overflow.
+pub const MAX_CALL_DEPTH: usize = 1_000;
+
+/// Rust stack for running Lox code. Each Lox call takes up to about 20 KiB of it in an
+/// unoptimized build, for a method calling itself from inside loops and lambdas, and under
+/// 4 KiB in a release build, so `MAX_CALL_DEPTH` calls fit with room to spare.
+pub const STACK_SIZE: usize = 32 * 1024 * 1024;
+
+/// Runs `f` on a thread with a `STACK_SIZE` stack and waits for it, so the Lox code it runs can
+/// nest calls up to `MAX_CALL_DEPTH` whatever the stack of the calling thread. Fails if the
+/// thread can't be started.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> io::Result<T> {
+    thread::scope(|scope| {
+        let thread = thread::Builder::new()
+            .name(String::from("lox"))
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)?;
+        Ok(thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+    })
+}
+
+/// Runs programs by walking their syntax tree. `print` writes to `out`. `globals` holds the
+/// built-in functions and `environment` is the innermost scope of the code being run; state
+/// persists between calls to `interpret`, as the REPL needs.
+#[derive(Debug)]
+pub struct Interpreter<W: Write = io::Stdout> {
+    out: W,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    call_depth: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_output(io::stdout())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl<W: Write> Interpreter<W> {
+    pub fn with_output(out: W) -> Self {
+        let mut globals = Environment::new();
+        let natives = [
+            NativeFunction {
+                name: "clock",
+                arity: 0,
+                function: function::clock,
+            },
+            NativeFunction {
+                name: "len",
+                arity: 1,
+                function: function::len,
+            },
+            NativeFunction {
+                name: "push",
+                arity: 2,
+                function: function::push,
+            },
+            NativeFunction {
+                name: "has",
+                arity: 2,
+                function: function::has,
+            },
+            NativeFunction {
+                name: "keys",
+                arity: 1,
+                function: function::keys,
+            },
+            NativeFunction {
+                name: "remove",
+                arity: 2,
+                function: function::remove,
+            },
+        ];
+        for native in natives {
+            globals.define(Symbol::intern(native.name), Value::Native(Rc::new(native)));
+        }
+        let globals = Rc::new(RefCell::new(globals));
+        Interpreter {
+            out,
+            environment: globals.clone(),
+            globals,
+            call_depth: 0,
+        }
+    }
+
+    pub fn globals(&self) -> &Rc<RefCell<Environment>> {
+        &self.globals
+    }
+
+    pub fn output(&self) -> &W {
+        &self.out
+    }
+
+    /// Runs every statement of `program`, stopping at the first runtime error.
+    pub fn interpret(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        for stmt in &program.statements {
+            match self.execute(stmt) {
+                Err(Unwind::Error(error)) => return Err(error),
+                // The parser rejects `return`, `break` and `continue` where they can get here.
+                Ok(()) | Err(Unwind::Return(_) | Unwind::Break(_) | Unwind::Continue(_)) => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn execute(&mut self, stmt: &Stmt) -> Execution {
+        stmt.accept(self)
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Evaluation {
+        expr.accept(self)
+    }
+
+    /// Runs `statements` in `environment`, then goes back to the current scope whether they
+    /// finished, failed or returned.
+    pub fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> Execution {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
+        self.environment = previous;
+        result
+    }
+
+    /// Runs the body of `function` in a new scope inside its closure, with the parameters bound
+    /// to `arguments`. Falling off the end returns `nil`, or `this` from an initializer.
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        arguments: Vec<Value>,
+        location: Location,
+    ) -> Evaluation {
+        if self.call_depth == MAX_CALL_DEPTH {
+            return Err(RuntimeError::new(location, "Stack overflow."));
+        }
+        let mut environment = Environment::with_enclosing(function.closure.clone());
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            environment.define(param.symbol, argument);
+        }
+
+        self.call_depth += 1;
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.call_depth -= 1;
+        match result {
+            Err(Unwind::Error(error)) => Err(error),
+            _ if function.is_initializer => function.closure.borrow().get(&Name {
+                symbol: Symbol::intern("this"),
+                location,
+            }),
+            Ok(()) | Err(Unwind::Break(_) | Unwind::Continue(_)) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+        }
+    }
+
+    /// Makes an instance of `class` and runs its initializer, if it has one, on it.
+    fn instantiate(
+        &mut self,
+        class: Rc<LoxClass>,
+        arguments: Vec<Value>,
+        location: Location,
+    ) -> Evaluation {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(class.clone())));
+        if let Some(init) = class.find_method(Symbol::intern("init")) {
+            self.call_function(&init.bind(instance.clone()), arguments, location)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+
+    fn number_operands(&mut self, expr: &BinaryExpr) -> Result<(f64, f64), RuntimeError> {
+        match (self.evaluate(&expr.left)?, self.evaluate(&expr.right)?) {
+            (Value::Number(left), Value::Number(right)) => Ok((left, right)),
+            _ => Err(RuntimeError::new(
+                expr.location,
+                "Operands must be numbers.",
+            )),
+        }
+    }
+}
+
+impl<W: Write> Visitor<Evaluation> for Interpreter<W> {
+    fn visit_binaryexpr(&mut self, expr: &BinaryExpr) -> Evaluation {
+        let number =
+            |(left, right): (f64, f64), op: fn(f64, f64) -> f64| Ok(Value::Number(op(left, right)));
+        let compare = |(left, right): (f64, f64), op: fn(&f64, &f64) -> bool| {
+            Ok(Value::Bool(op(&left, &right)))
+        };
+
+        match expr.operator {
+            Operator::Plus => match (self.evaluate(&expr.left)?, self.evaluate(&expr.right)?) {
+                (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
+                (Value::Str(left), Value::Str(right)) => {
+                    Ok(Value::Str(Rc::from(format!("{}{}", left, right))))
+                }
+                _ => Err(RuntimeError::new(
+                    expr.location,
+                    "Operands must be two numbers or two strings.",
+                )),
+            },
+            Operator::Minus => number(self.number_operands(expr)?, |a, b| a - b),
+            Operator::Star => number(self.number_operands(expr)?, |a, b| a * b),
+            Operator::Slash => number(self.number_operands(expr)?, |a, b| a / b),
+            Operator::Greater => compare(self.number_operands(expr)?, f64::gt),
+            Operator::GreaterEqual => compare(self.number_operands(expr)?, f64::ge),
+            Operator::Less => compare(self.number_operands(expr)?, f64::lt),
+            Operator::LessEqual => compare(self.number_operands(expr)?, f64::le),
+            Operator::EqualEqual => Ok(Value::Bool(
+                self.evaluate(&expr.left)? == self.evaluate(&expr.right)?,
+            )),
+            Operator::Comma => {
+                self.evaluate(&expr.left)?;
+                self.evaluate(&expr.right)
+            }
+            Operator::BangEqual => Ok(Value::Bool(
+                self.evaluate(&expr.left)? != self.evaluate(&expr.right)?,
+            )),
+            operator => Err(RuntimeError::new(
+                expr.location,
+                format!("'{}' is not a binary operator.", operator.to_token()),
+            )),
+        }
+    }
+
+    fn visit_unaryexpr(&mut self, expr: &UnaryExpr) -> Evaluation {
+        let value = self.evaluate(&expr.expression)?;
+        match (expr.operator, value) {
+            (UnaryOperator::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+            (UnaryOperator::Minus, _) => Err(RuntimeError::new(
+                expr.location,
+                "Operand must be a number.",
+            )),
+            (UnaryOperator::Bang, value) => Ok(Value::Bool(!value.is_truthy())),
+        }
+    }
+
+    fn visit_groupingexpr(&mut self, expr: &GroupingExpr) -> Evaluation {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_literalexpr(&mut self, expr: &LiteralExpr) -> Evaluation {
+        Ok(Value::from(&expr.value))
+    }
+
+    fn visit_interpolationexpr(&mut self, expr: &InterpolationExpr) -> Evaluation {
+        let mut text = String::new();
+        for part in &expr.parts {
+            text.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::Str(Rc::from(text)))
+    }
+
+    fn visit_variableexpr(&mut self, expr: &VariableExpr) -> Evaluation {
+        self.environment.borrow().get(&expr.name)
+    }
+
+    fn visit_assignexpr(&mut self, expr: &AssignExpr) -> Evaluation {
+        let value = self.evaluate(&expr.value)?;
+        self.environment
+            .borrow_mut()
+            .assign(&expr.name, value.clone())?;
+        Ok(value)
+    }
+
+    fn visit_callexpr(&mut self, expr: &CallExpr) -> Evaluation {
+        let callee = self.evaluate(&expr.callee)?;
+        let arguments = expr
+            .arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::Native(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    expr.location,
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::new(
+                expr.location,
+                format!("Expected {} arguments but got {}.", arity, arguments.len()),
+            ));
+        }
+
+        match callee {
+            Value::Function(function) => self.call_function(&function, arguments, expr.location),
+            Value::Native(native) => (native.function)(&arguments)
+                .map_err(|message| RuntimeError::new(expr.location, message)),
+            Value::Class(class) => self.instantiate(class, arguments, expr.location),
+            _ => unreachable!("checked above"),
+        }
+    }
+
+    fn visit_lambdaexpr(&mut self, expr: &LambdaExpr) -> Evaluation {
+        Ok(Value::Function(Rc::new(LoxFunction {
+            declaration: expr.declaration.clone(),
+            closure: self.environment.clone(),
+            is_initializer: false,
+        })))
+    }
+
+    fn visit_listexpr(&mut self, expr: &ListExpr) -> Evaluation {
+        let elements = expr
+            .elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_mapexpr(&mut self, expr: &MapExpr) -> Evaluation {
+        let mut map = Map::new();
+        for (key, value) in &expr.entries {
+            let key = map_key(&self.evaluate(key)?, expr.location)?;
+            map.insert(key, self.evaluate(value)?);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn visit_indexexpr(&mut self, expr: &IndexExpr) -> Evaluation {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        match object {
+            Value::List(list) => {
+                let list = list.borrow();
+                let index = list_index(&index, list.len(), false, expr.location)?;
+                Ok(list[index].clone())
+            }
+            Value::Map(map) => {
+                let key = map_key(&index, expr.location)?;
+                map.borrow().get(&key).cloned().ok_or_else(|| {
+                    RuntimeError::new(expr.location, format!("Key '{}' is not in the map.", key))
+                })
+            }
+            _ => Err(RuntimeError::new(
+                expr.location,
+                "Only lists and maps can be indexed.",
+            )),
+        }
+    }
+
+    /// Assigning to a key a map doesn't have adds it.
+    fn visit_indexsetexpr(&mut self, expr: &IndexSetExpr) -> Evaluation {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        let value = self.evaluate(&expr.value)?;
+        match object {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = list_index(&index, list.len(), false, expr.location)?;
+                list[index] = value.clone();
+            }
+            Value::Map(map) => {
+                let key = map_key(&index, expr.location)?;
+                map.borrow_mut().insert(key, value.clone());
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    expr.location,
+                    "Only lists and maps can be indexed.",
+                ))
+            }
+        }
+        Ok(value)
+    }
+
+    /// A new list with the elements from `start` up to but not including `end`. It is empty
+    /// if `end` comes first.
+    fn visit_sliceexpr(&mut self, expr: &SliceExpr) -> Evaluation {
+        let Value::List(list) = self.evaluate(&expr.object)? else {
+            return Err(RuntimeError::new(
+                expr.location,
+                "Only lists can be sliced.",
+            ));
+        };
+        let start = expr
+            .start
+            .as_ref()
+            .map(|start| self.evaluate(start))
+            .transpose()?;
+        let end = expr
+            .end
+            .as_ref()
+            .map(|end| self.evaluate(end))
+            .transpose()?;
+
+        let list = list.borrow();
+        let bound = |bound: Option<Value>, default: usize| match bound {
+            Some(bound) => list_index(&bound, list.len(), true, expr.location),
+            None => Ok(default),
+        };
+        let start = bound(start, 0)?;
+        let end = bound(end, list.len())?.max(start);
+        Ok(Value::List(Rc::new(RefCell::new(
+            list[start..end].to_vec(),
+        ))))
+    }
+
+    fn visit_getexpr(&mut self, expr: &GetExpr) -> Evaluation {
+        match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, &expr.name),
+            _ => Err(RuntimeError::new(
+                expr.name.location(),
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn visit_setexpr(&mut self, expr: &SetExpr) -> Evaluation {
+        let Value::Instance(instance) = self.evaluate(&expr.object)? else {
+            return Err(RuntimeError::new(
+                expr.name.location(),
+                "Only instances have fields.",
+            ));
+        };
+        let value = self.evaluate(&expr.value)?;
+        instance.borrow_mut().set(&expr.name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_thisexpr(&mut self, expr: &ThisExpr) -> Evaluation {
+        self.environment.borrow().get(&expr.keyword)
+    }
+
+    /// The superclass is in a scope of its own around the methods, and `this` in the scope
+    /// of the bound method, just inside it.
+    fn visit_superexpr(&mut self, expr: &SuperExpr) -> Evaluation {
+        let environment = self.environment.borrow();
+        let this = Name {
+            symbol: Symbol::intern("this"),
+            ..expr.keyword
+        };
+        let (Value::Class(superclass), Value::Instance(instance)) =
+            (environment.get(&expr.keyword)?, environment.get(&this)?)
+        else {
+            // The parser only allows `super` in methods of subclasses, but a tree built some
+            // other way could have it anywhere.
+            return Err(RuntimeError::new(
+                expr.keyword.location(),
+                "Can't use 'super' outside of a method of a subclass.",
+            ));
+        };
+        match superclass.find_method(expr.method.symbol) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+            None => Err(RuntimeError::new(
+                expr.method.location(),
+                format!("Undefined property '{}'.", expr.method.symbol),
+            )),
+        }
+    }
+
+    fn visit_ternaryexpr(&mut self, expr: &TernaryExpr) -> Evaluation {
+        if self.evaluate(&expr.condition)?.is_truthy() {
+            self.evaluate(&expr.then_branch)
+        } else {
+            self.evaluate(&expr.else_branch)
+        }
+    }
+
+    fn visit_logicalexpr(&mut self, expr: &LogicalExpr) -> Evaluation {
+        let left = self.evaluate(&expr.left)?;
+        let decided = match expr.operator {
+            LogicalOperator::Or => left.is_truthy(),
+            LogicalOperator::And => !left.is_truthy(),
+        };
+        if decided {
+            Ok(left)
+        } else {
+            self.evaluate(&expr.right)
+        }
+    }
+}
+
+impl<W: Write> StmtVisitor<Execution> for Interpreter<W> {
+    fn visit_expressionstmt(&mut self, stmt: &ExpressionStmt) -> Execution {
+        self.evaluate(&stmt.expression)?;
+        Ok(())
+    }
+
+    fn visit_printstmt(&mut self, stmt: &PrintStmt) -> Execution {
+        let value = self.evaluate(&stmt.expression)?;
+        writeln!(self.out, "{}", value)
+            .map_err(|e| RuntimeError::new(stmt.location, format!("Could not print: {}.", e)))?;
+        Ok(())
+    }
+
+    fn visit_varstmt(&mut self, stmt: &VarStmt) -> Execution {
+        let value = match &stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.symbol, value);
+        Ok(())
+    }
+
+    fn visit_blockstmt(&mut self, stmt: &BlockStmt) -> Execution {
+        let environment = Environment::with_enclosing(self.environment.clone());
+        self.execute_block(&stmt.statements, environment)
+    }
+
+    fn visit_ifstmt(&mut self, stmt: &IfStmt) -> Execution {
+        if self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_whilestmt(&mut self, stmt: &WhileStmt) -> Execution {
+        let label = stmt.label.map(|label| label.symbol);
+        let targets = |target: Option<Symbol>| target.is_none() || target == label;
+        while self.evaluate(&stmt.condition)?.is_truthy() {
+            match self.execute(&stmt.body) {
+                Err(Unwind::Break(target)) if targets(target) => break,
+                Err(Unwind::Continue(target)) if targets(target) => {}
+                result => result?,
+            }
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_breakstmt(&mut self, stmt: &BreakStmt) -> Execution {
+        Err(Unwind::Break(stmt.label.map(|label| label.symbol)))
+    }
+
+    fn visit_continuestmt(&mut self, stmt: &ContinueStmt) -> Execution {
+        Err(Unwind::Continue(stmt.label.map(|label| label.symbol)))
+    }
+
+    fn visit_functionstmt(&mut self, stmt: &Rc<FunctionStmt>) -> Execution {
+        let function = LoxFunction {
+            declaration: stmt.clone(),
+            closure: self.environment.clone(),
+            is_initializer: false,
+        };
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.symbol, Value::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_classstmt(&mut self, stmt: &ClassStmt) -> Execution {
+        let superclass = match &stmt.superclass {
+            Some(superclass) => match self.environment.borrow().get(&superclass.name)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        superclass.name.location(),
+                        "Superclass must be a class.",
+                    )
+                    .into())
+                }
+            },
+            None => None,
+        };
+
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::with_enclosing(self.environment.clone());
+                environment.define(Symbol::intern("super"), Value::Class(superclass.clone()));
+                Rc::new(RefCell::new(environment))
+            }
+            None => self.environment.clone(),
+        };
+        let init = Symbol::intern("init");
+        let methods: HashMap<_, _> = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let function = LoxFunction {
+                    declaration: method.clone(),
+                    closure: closure.clone(),
+                    is_initializer: method.name.symbol == init,
+                };
+                (method.name.symbol, Rc::new(function))
+            })
+            .collect();
+
+        let class = LoxClass {
+            name: stmt.name.symbol,
+            superclass,
+            methods,
+        };
+        self.environment
+            .borrow_mut()
+            .define(stmt.name.symbol, Value::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    fn visit_returnstmt(&mut self, stmt: &ReturnStmt) -> Execution {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+}
+
+fn map_key(key: &Value, location: Location) -> Result<MapKey, RuntimeError> {
+    MapKey::try_from(key).map_err(|message| RuntimeError::new(location, message))
+}
+
+/// Where `index` points in a list of `len` elements, counting back from the end if it is
+/// negative. A slice bound may also be `len`, just past the last element.
+fn list_index(
+    index: &Value,
+    len: usize,
+    slice_bound: bool,
+    location: Location,
+) -> Result<usize, RuntimeError> {
+    let &Value::Number(index) = index else {
+        return Err(RuntimeError::new(location, "List index must be a number."));
+    };
+    if index.fract() != 0.0 {
+        return Err(RuntimeError::new(
+            location,
+            format!("List index must be an integer, not {}.", index),
+        ));
+    }
+
+    let resolved = if index < 0.0 {
+        index + len as f64
+    } else {
+        index
+    };
+    let limit = if slice_bound { len + 1 } else { len };
+    if resolved < 0.0 || resolved >= limit as f64 {
+        return Err(RuntimeError::new(
+            location,
+            format!(
+                "Index {} is out of range for a list of length {}.",
+                index, len
+            ),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::expressions::LiteralValue;
+    use crate::parser::parser::Parser;
+    use crate::scanner::scanner::Scanner;
+    use crate::scanner::source::{SourceMap, Span};
+
+    fn run(source: &str) -> Result<String, (String, RuntimeError)> {
+        with_stack(|| {
+            let tokens = Scanner::new(String::from(source)).scan_tokens().unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            let mut interpreter = Interpreter::with_output(Vec::new());
+            let result = interpreter.interpret(&program);
+            let output = String::from_utf8(interpreter.output().clone()).unwrap();
+            match result {
+                Ok(()) => Ok(output),
+                Err(error) => Err((output, error)),
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(
+            run("print 1 + 2 * 3; print \"a\" + \"b\"; print -(4 / 8); print !nil;").unwrap(),
+            "7\nab\n-0.5\ntrue\n"
+        );
+        assert_eq!(
+            run("print 1 == 1; print \"1\" == 1; print nil != false; print 2 <= 1;").unwrap(),
+            "true\nfalse\ntrue\nfalse\n"
+        );
+        assert_eq!(
+            run("print \"${1 + 1} is ${true}\";").unwrap(),
+            "2 is true\n"
+        );
+    }
+
+    #[test]
+    fn test_errors_render_at_their_span() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("test.lox", "var a = 1;\nprint a + \"é\";");
+        let tokens = Scanner::with_file(sources.file(file).source.clone(), file)
+            .scan_tokens()
+            .unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let error = Interpreter::with_output(Vec::new())
+            .interpret(&program)
+            .unwrap_err();
+
+        assert_eq!((error.file, error.span), (file, Span::new(19, 20)));
+        assert_eq!(
+            sources.render(error.file, error.span, &error.message),
+            "test.lox:2:9: Operands must be two numbers or two strings.\nprint a + \"é\";\n        ^"
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        assert_eq!(
+            run("if (1 < 2) print \"yes\"; else print \"no\";").unwrap(),
+            "yes\n"
+        );
+        assert_eq!(
+            run("if (nil) print \"yes\"; else print \"no\";").unwrap(),
+            "no\n"
+        );
+        assert_eq!(run("if (false) print 1;").unwrap(), "");
+        // The else goes with the inner if.
+        assert_eq!(
+            run("if (true) if (false) print 1; else print 2;").unwrap(),
+            "2\n"
+        );
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() {
+        assert_eq!(
+            run("print nil or \"default\"; print 1 or -\"x\"; print false and -\"x\";").unwrap(),
+            "default\n1\nfalse\n"
+        );
+        assert_eq!(run("print 1 and 2; print nil and 2;").unwrap(), "2\nnil\n");
+
+        let (output, error) = run("print true and -\"x\";").unwrap_err();
+        assert_eq!(output, "");
+        assert_eq!(
+            error.to_string(),
+            "[line 1:16] Error: Operand must be a number."
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        assert_eq!(run("while (false) print 1; print 2;").unwrap(), "2\n");
+        assert_eq!(run("for (; false;) print 1;").unwrap(), "");
+
+        let (output, error) = run("for (;;) { print 1; print 1 + nil; }").unwrap_err();
+        assert_eq!(output, "1\n");
+        assert_eq!(
+            error.to_string(),
+            "[line 1:29] Error: Operands must be two numbers or two strings."
+        );
+    }
+
+    #[test]
+    fn test_for_loop_clauses_run_in_order() {
+        // No variables: each clause shows it ran by failing, after what ran before it.
+        let (output, error) = run("for (-\"init\"; true; 1) print 1;").unwrap_err();
+        assert_eq!((output.as_str(), error.col), ("", 6));
+        let (output, error) = run("for (1; -\"cond\"; 1) print 1;").unwrap_err();
+        assert_eq!((output.as_str(), error.col), ("", 9));
+        let (output, error) = run("for (1; true; -\"step\") print \"body\";").unwrap_err();
+        assert_eq!((output.as_str(), error.col), ("body\n", 15));
+        assert_eq!(error.message, "Operand must be a number.");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let (output, error) = run("print 1;\nprint \"a\" < 2;\nprint 3;").unwrap_err();
+        assert_eq!(output, "1\n");
+        assert_eq!(
+            error.to_string(),
+            "[line 2:11] Error: Operands must be numbers."
+        );
+    }
+
+    #[test]
+    fn test_variables_and_assignment() {
+        assert_eq!(
+            run("var a = 1; var b; print b; b = a = a + 1; print a; print b;").unwrap(),
+            "nil\n2\n2\n"
+        );
+        assert_eq!(
+            run("var i = 0; var sum = 0; while (i < 4) { sum = sum + i; i = i + 1; } print sum;")
+                .unwrap(),
+            "6\n"
+        );
+        assert_eq!(
+            run("for (var i = 0; i < 3; i = i + 1) print i;").unwrap(),
+            "0\n1\n2\n"
+        );
+    }
+
+    #[test]
+    fn test_block_scopes_and_shadowing() {
+        let source = "var a = \"global\";
+            {
+                var a = \"outer\";
+                {
+                    var a = \"inner\";
+                    print a;
+                }
+                print a;
+                a = \"changed\";
+            }
+            print a;
+            { a = \"assigned\"; }
+            print a;";
+        assert_eq!(run(source).unwrap(), "inner\nouter\nglobal\nassigned\n");
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        let (output, error) = run("var a = 1;\nprint a;\nprint b;").unwrap_err();
+        assert_eq!(output, "1\n");
+        assert_eq!(
+            error.to_string(),
+            "[line 3:7] Error: Undefined variable 'b'."
+        );
+
+        let (_, error) = run("{ var c = 1; }\n  c = 2;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:3] Error: Undefined variable 'c'."
+        );
+    }
+
+    #[test]
+    fn test_scope_is_restored_after_an_error() {
+        let tokens = Scanner::new(String::from("var a = 1; { var a = 2; print nope; }"))
+            .scan_tokens()
+            .unwrap();
+        let mut interpreter = Interpreter::with_output(Vec::new());
+        interpreter
+            .interpret(&Parser::new(tokens).parse().unwrap())
+            .unwrap_err();
+
+        let tokens = Scanner::new(String::from("print a;"))
+            .scan_tokens()
+            .unwrap();
+        interpreter
+            .interpret(&Parser::new(tokens).parse().unwrap())
+            .unwrap();
+        assert_eq!(interpreter.output(), b"1\n");
+    }
+
+    #[test]
+    fn test_fib_data_file() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test/fib.lox");
+        let source = std::fs::read_to_string(path).unwrap();
+        assert_eq!(run(&source).unwrap(), "0\n1\n1\n2\n3\n5\n8\n13\n21\n34\n");
+    }
+
+    #[test]
+    fn test_functions_and_return() {
+        assert_eq!(
+            run("fun greet(name) { print \"hi \" + name; } print greet(\"bob\"); print greet;")
+                .unwrap(),
+            "hi bob\nnil\n<fn greet>\n"
+        );
+        // A return inside loops and blocks leaves the whole function.
+        assert_eq!(
+            run("fun first() { while (true) { for (;;) { return 1; } } } print first();").unwrap(),
+            "1\n"
+        );
+        assert_eq!(
+            run("print clock; print clock() > 0;").unwrap(),
+            "<native fn>\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "fun makeCounter() {
+              var i = 0;
+              fun count() { i = i + 1; return i; }
+              return count;
+            }
+            var a = makeCounter();
+            var b = makeCounter();
+            print a(); print a(); print b();";
+        assert_eq!(run(source).unwrap(), "1\n2\n1\n");
+
+        // Closures see the scope they were declared in, not the caller's.
+        let source = "var x = \"global\";
+            fun show() { print x; }
+            { var x = \"block\"; show(); }";
+        assert_eq!(run(source).unwrap(), "global\n");
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let (_, error) = run("fun f(a, b) {}\nf(1);").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:4] Error: Expected 2 arguments but got 1."
+        );
+
+        let (_, error) = run("\"text\"();").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:8] Error: Can only call functions and classes."
+        );
+
+        let (_, error) = run("fun loop() { loop(); } loop();").unwrap_err();
+        assert_eq!(error.to_string(), "[line 1:19] Error: Stack overflow.");
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let sum = "fun sum(n) { if (n == 0) return 0; return n + sum(n - 1); }";
+        let n = MAX_CALL_DEPTH - 1;
+        assert_eq!(
+            run(&format!("{} print sum({});", sum, n)).unwrap(),
+            format!("{}\n", n * (n + 1) / 2)
+        );
+        let (_, error) = run(&format!("{} print sum({});", sum, n + 1)).unwrap_err();
+        assert_eq!(error.message, "Stack overflow.");
+
+        let source = format!(
+            "class A {{
+              m(n) {{
+                if (n == 0) return 0;
+                {{ while (true) {{ for (;;) {{
+                  var f = (k) => k;
+                  return f(1) + [this.m(n - 1)][0];
+                }} }} }}
+              }}
+            }}
+            print A().m({});",
+            n
+        );
+        assert_eq!(run(&source).unwrap(), format!("{}\n", n));
+    }
+
+    #[test]
+    fn test_classes_and_instances() {
+        let source = "class Point {
+              init(x, y) { this.x = x; this.y = y; }
+              sum() { return this.x + this.y; }
+            }
+            var p = Point(1, 2);
+            print Point; print p; print p.sum();
+            p.x = 10;
+            var sum = p.sum;
+            print sum();
+            p.sum = \"field\";
+            print p.sum;
+            print p.init(3, 4) == p; print p.x;";
+        assert_eq!(
+            run(source).unwrap(),
+            "Point\nPoint instance\n3\n12\nfield\ntrue\n3\n"
+        );
+        assert_eq!(
+            run("class A { init() { this.a = 1; return; } } print A().a;").unwrap(),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn test_inheritance_and_super() {
+        let source = "class A {
+              name() { return \"A\"; }
+              greet() { return \"hi from \" + this.name(); }
+            }
+            class B < A {
+              name() { return \"B\"; }
+              greet() { return super.greet() + \" via \" + super.name(); }
+            }
+            class C < B {}
+            print C().greet();";
+        assert_eq!(run(source).unwrap(), "hi from B via A\n");
+    }
+
+    #[test]
+    fn test_super_outside_a_subclass_method() {
+        // The parser rejects this, so the tree is built by hand: `super` and `this` are plain
+        // variables, and `super` isn't a class.
+        let name = |text: &str, col| Name {
+            symbol: Symbol::intern(text),
+            location: Location {
+                line: 1,
+                col,
+                ..Location::default()
+            },
+        };
+        let var = |text, col| {
+            Stmt::Var(VarStmt {
+                name: name(text, col),
+                initializer: Some(Expr::Literal(LiteralExpr::new(LiteralValue::Nil))),
+            })
+        };
+        let program = Program {
+            statements: vec![
+                var("super", 1),
+                var("this", 2),
+                Stmt::Expression(ExpressionStmt {
+                    expression: Expr::Super(SuperExpr {
+                        keyword: name("super", 3),
+                        method: name("m", 9),
+                    }),
+                }),
+            ],
+            ranges: vec![0..0; 3],
+        };
+        let error = Interpreter::with_output(Vec::new())
+            .interpret(&program)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:3] Error: Can't use 'super' outside of a method of a subclass."
+        );
+    }
+
+    #[test]
+    fn test_class_errors() {
+        let (_, error) = run("var A = 1;\nclass B < A {}").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:11] Error: Superclass must be a class."
+        );
+
+        let (_, error) = run("class A {}\nprint A().nope;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:11] Error: Undefined property 'nope'."
+        );
+
+        let (_, error) = run("print \"text\".length;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:14] Error: Only instances have properties."
+        );
+
+        let (_, error) = run("var a = 1;\na.b = 2;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:3] Error: Only instances have fields."
+        );
+
+        let (_, error) = run("class A { init(a) {} }\nA();").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:3] Error: Expected 1 arguments but got 0."
+        );
+
+        let (_, error) =
+            run("class A {}\nclass B < A { m() { super.m(); } }\nB().m();").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:27] Error: Undefined property 'm'."
+        );
+    }
+
+    #[test]
+    fn test_anonymous_functions() {
+        let source = "fun apply(f, a, b) { return f(a, b); }
+            print apply(fun (a, b) { return a + b; }, 1, 2);
+            print apply((a, b) => a * b, 3, 4);
+            var twice = (f) => (x) => f(f(x));
+            print twice((x) => x + 1)(5);
+            print fun () {};";
+        assert_eq!(run(source).unwrap(), "3\n12\n7\n<fn lambda>\n");
+
+        let source = "fun counter() { var i = 0; return () => { i = i + 1; return i; }; }
+            var next = counter();
+            next();
+            print next();";
+        assert_eq!(run(source).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn test_lists() {
+        let source = "var xs = [1, \"two\", nil, [3]];
+            print xs; print xs[1]; print xs[-1][0];
+            xs[0] = xs[0] + 10; xs[-2] = true;
+            print xs;
+            print xs[1:3]; print xs[:-1]; print xs[2:]; print xs[3:1]; print xs[:];
+            var ys = xs; ys[0] = 0;
+            print xs[0]; print xs == ys; print xs[:] == xs; print [] == [];";
+        assert_eq!(
+            run(source).unwrap(),
+            "[1, two, nil, [3]]\ntwo\n3\n[11, two, true, [3]]\n\
+             [two, true]\n[11, two, true]\n[true, [3]]\n[]\n[11, two, true, [3]]\n\
+             0\ntrue\nfalse\nfalse\n"
+        );
+        assert_eq!(
+            run("var xs = [1]; xs[0] = xs; print xs;").unwrap(),
+            "[[...]]\n"
+        );
+    }
+
+    #[test]
+    fn test_list_errors() {
+        let (_, error) = run("var xs = [1, 2, 3];\nprint xs[3];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:9] Error: Index 3 is out of range for a list of length 3."
+        );
+
+        let (_, error) = run("var xs = [1, 2, 3];\nxs[-4] = 0;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:3] Error: Index -4 is out of range for a list of length 3."
+        );
+
+        let (_, error) = run("print [1, 2][0:5];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:13] Error: Index 5 is out of range for a list of length 2."
+        );
+
+        let (_, error) = run("print [1][0.5];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:10] Error: List index must be an integer, not 0.5."
+        );
+
+        let (_, error) = run("print [1][\"0\"];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:10] Error: List index must be a number."
+        );
+
+        let (_, error) = run("var s = \"abc\";\nprint s[0];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:8] Error: Only lists and maps can be indexed."
+        );
+    }
+
+    #[test]
+    fn test_list_natives() {
+        let source = "var xs = [];
+            for (var i = 0; i < 3; i = i + 1) push(xs, i * i);
+            print xs; print len(xs); print len(\"año\"); print push([], 1);";
+        assert_eq!(run(source).unwrap(), "[0, 1, 4]\n3\n3\n[1]\n");
+
+        let (_, error) = run("print len(1);").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:12] Error: Can't take the length of a number."
+        );
+    }
+
+    #[test]
+    fn test_maps() {
+        let source = "var key = \"b\";
+            var m = {\"a\": 1, key: 2, 3: \"three\", true: nil, nil: false,};
+            print m; print m[\"b\"]; print m[1 + 2]; print m[true]; print {};
+            m[\"a\"] = 10; m[0] = \"zero\"; m[-0] = \"still zero\";
+            print m;
+            print has(m, \"a\"); print has(m, \"z\"); print has(m, true); print len(m);
+            print remove(m, \"a\"); print remove(m, \"a\"); print keys(m);";
+        assert_eq!(
+            run(source).unwrap(),
+            "{a: 1, b: 2, 3: three, true: nil, nil: false}\n2\nthree\nnil\n{}\n\
+             {a: 10, b: 2, 3: three, true: nil, nil: false, 0: still zero}\n\
+             true\nfalse\ntrue\n6\n\
+             10\nnil\n[b, 3, true, nil, 0]\n"
+        );
+
+        // Iterating in insertion order through the keys.
+        let source = "var m = {\"z\": 1, \"a\": 2, \"m\": 3};
+            var ks = keys(m);
+            for (var i = 0; i < len(ks); i = i + 1) print ks[i] + \"=\" + \"${m[ks[i]]}\";";
+        assert_eq!(run(source).unwrap(), "z=1\na=2\nm=3\n");
+    }
+
+    #[test]
+    fn test_map_errors() {
+        let (_, error) = run("var m = {\"a\": 1};\nprint m[\"b\"];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:8] Error: Key 'b' is not in the map."
+        );
+
+        let (_, error) = run("var m = {};\nm[[]] = 1;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 2:2] Error: A list can't be a map key."
+        );
+
+        let (_, error) = run("print {0/0: 1};").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:7] Error: NaN can't be a map key."
+        );
+
+        let (_, error) = run("print {}[0:1];").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 1:9] Error: Only lists can be sliced."
+        );
+    }
+
+    #[test]
+    fn test_ternary_and_comma_operators() {
+        assert_eq!(
+            run("print 1 < 2 ? \"yes\" : \"no\"; print nil ? 1 : false ? 2 : 3;").unwrap(),
+            "yes\n3\n"
+        );
+        // Only the chosen branch runs.
+        assert_eq!(
+            run("var a = 0; true ? a = 1 : -\"x\"; print a;").unwrap(),
+            "1\n"
+        );
+        assert_eq!(
+            run("var a; var b = (a = 1, a + 1); print a; print b;").unwrap(),
+            "1\n2\n"
+        );
+        assert_eq!(
+            run("var j = 3; for (var i = 0; i < j; i = i + 1, j = j - 1) print i * 10 + j;")
+                .unwrap(),
+            "3\n12\n"
+        );
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        // `continue` in a `for` still runs the increment, through the nested blocks.
+        let source = "for (var i = 0; i < 6; i = i + 1) {
+              { if (i == 1) continue; }
+              if (i == 4) { { break; } }
+              print i;
+            }";
+        assert_eq!(run(source).unwrap(), "0\n2\n3\n");
+
+        let source = "var i = 0;
+            while (true) { i = i + 1; if (i < 3) continue; print i; break; }";
+        assert_eq!(run(source).unwrap(), "3\n");
+
+        let source = "outer: for (var i = 0; i < 3; i = i + 1) {
+              for (var j = 0; j < 3; j = j + 1) {
+                if (j == 1) continue outer;
+                if (i == 2) break outer;
+                print i * 10 + j;
+              }
+            }
+            print \"done\";";
+        assert_eq!(run(source).unwrap(), "0\n10\ndone\n");
+
+        // A loop inside a function called from a loop only stops its own loop.
+        let source =
+            "fun first(xs) { for (var i = 0; i < len(xs); i = i + 1) { if (xs[i]) return i; } }
+            for (var n = 0; n < 2; n = n + 1) { print first([false, true]); }";
+        assert_eq!(run(source).unwrap(), "1\n1\n");
+    }
+}
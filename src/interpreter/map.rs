@@ -0,0 +1,74 @@
+use std::fmt;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+
+use super::value::Value;
+
+/// A Lox map. Entries keep the order their keys were first inserted in.
+pub type Map = IndexMap<MapKey, Value>;
+
+/// A value that can be a map key. Two keys are the same exactly when the values are equal in
+/// Lox, so numbers are compared by value with `-0` the same as `0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    /// The bits of a number that isn't NaN, with `-0` stored as `0`.
+    Number(u64),
+    Str(Rc<str>),
+}
+
+impl TryFrom<&Value> for MapKey {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Bool(b) => Ok(MapKey::Bool(*b)),
+            // NaN is not equal to itself, so it could never be looked up again.
+            Value::Number(n) if n.is_nan() => Err(String::from("NaN can't be a map key.")),
+            Value::Number(n) => Ok(MapKey::Number((n + 0.0).to_bits())),
+            Value::Str(s) => Ok(MapKey::Str(s.clone())),
+            value => Err(format!("A {} can't be a map key.", value.type_name())),
+        }
+    }
+}
+
+impl From<&MapKey> for Value {
+    fn from(key: &MapKey) -> Self {
+        match key {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::Str(s) => Value::Str(s.clone()),
+        }
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Value::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(value: Value) -> MapKey {
+        MapKey::try_from(&value).unwrap()
+    }
+
+    #[test]
+    fn test_keys_follow_lox_equality() {
+        assert_eq!(key(Value::Number(0.0)), key(Value::Number(-0.0)));
+        assert_eq!(key(Value::Number(1.0)), key(Value::Number(1.0)));
+        assert_ne!(key(Value::Number(1.0)), key(Value::Str(Rc::from("1"))));
+        assert_ne!(key(Value::Nil), key(Value::Bool(false)));
+        assert_eq!(
+            MapKey::try_from(&Value::Number(f64::NAN)).unwrap_err(),
+            "NaN can't be a map key."
+        );
+    }
+}
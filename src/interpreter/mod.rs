@@ -0,0 +1,8 @@
+pub mod class;
+pub mod environment;
+pub mod error;
+pub mod function;
+#[allow(clippy::module_inception)]
+pub mod interpreter;
+pub mod map;
+pub mod value;
@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use super::class::{LoxClass, LoxInstance};
+use super::function::{LoxFunction, NativeFunction};
+use super::map::Map;
+use crate::parser::expressions::LiteralValue;
+
+/// A Lox value at runtime.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+}
+
+impl Value {
+    /// `nil` and `false` are false; every other value is true.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+impl From<&LiteralValue> for Value {
+    fn from(literal: &LiteralValue) -> Self {
+        match literal {
+            LiteralValue::Number(n) => Value::Number(*n),
+            LiteralValue::Str(s) => Value::Str(Rc::from(s.as_str())),
+            LiteralValue::Bool(b) => Value::Bool(*b),
+            LiteralValue::Nil => Value::Nil,
+        }
+    }
+}
+
+/// Lox equality: values of different types are never equal, and functions, classes,
+/// instances, lists and maps are only equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => f.write_str("nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => f.write_str(s),
+            Value::Function(function) => write!(f, "<fn {}>", function.declaration.name.symbol),
+            Value::Native(_) => f.write_str("<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            // A list or map can contain itself, and is already borrowed if it does.
+            Value::List(list) => match list.try_borrow_mut() {
+                Ok(elements) => {
+                    let elements = elements.iter().map(|element| element.to_string());
+                    write!(f, "[{}]", elements.collect::<Vec<_>>().join(", "))
+                }
+                Err(_) => f.write_str("[...]"),
+            },
+            Value::Map(map) => match map.try_borrow_mut() {
+                Ok(entries) => {
+                    let entries = entries
+                        .iter()
+                        .map(|(key, value)| format!("{}: {}", key, value));
+                    write!(f, "{{{}}}", entries.collect::<Vec<_>>().join(", "))
+                }
+                Err(_) => f.write_str("{...}"),
+            },
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod interpreter;
+pub mod loxcli;
+pub mod parser;
+pub mod scanner;
@@ -1,7 +1,14 @@
-use anyhow::{Ok, Result};
+use super::trace;
+use crate::interpreter::interpreter::{with_stack, Interpreter};
+use crate::parser::{astprinter::Astprinter, parser::Parser as LoxParser};
+use crate::scanner::scanner::Scanner;
+use crate::scanner::source::{FileId, SourceMap};
+use anyhow::{bail, Context, Result};
 use clap::{Parser, Subcommand};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, info};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -9,12 +16,28 @@ pub struct Cli {
     #[arg(short, long, value_name = "FILE")]
     file: Option<PathBuf>,
 
+    /// Trace the given parts of the interpreter: a comma-separated list of
+    /// scanner, parser, cli or all, each optionally followed by =LEVEL
+    #[arg(long, value_name = "TARGETS", global = true)]
+    trace: Option<String>,
+
+    /// Level used for traced targets that don't name their own
+    #[arg(long, value_name = "LEVEL", default_value = "trace", global = true)]
+    trace_level: LevelFilter,
+
+    /// Write traces to this file instead of stderr
+    #[arg(long, value_name = "FILE", global = true)]
+    trace_file: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Run a Lox script
+    Run { file: PathBuf },
+
     /// Start an interactive session
     #[command(name = "repl")]
     Interactive,
@@ -23,13 +46,23 @@ enum Commands {
 pub fn run_command() -> Result<()> {
     let cli = Cli::parse();
 
+    if let Some(spec) = &cli.trace {
+        trace::init(spec, cli.trace_level, cli.trace_file.as_deref())?;
+    }
+    debug!(target: "cli", ?cli, "parsed arguments");
+
+    // Lox code runs on a thread of its own, with a stack big enough for deep recursion.
+    with_stack(|| run(&cli)).context("Could not start a thread to run Lox on.")?
+}
+
+fn run(cli: &Cli) -> Result<()> {
     if let Some(loxfile) = &cli.file {
-        println!("{:?}", loxfile);
-        return Ok(()); // Termina la función aquí si existe el archivo
+        return run_file(loxfile);
     };
 
     if let Some(command) = &cli.command {
         match command {
+            Commands::Run { file } => run_file(file)?,
             Commands::Interactive => interactive_session(),
         }
     } else {
@@ -40,8 +73,65 @@ pub fn run_command() -> Result<()> {
     Ok(())
 }
 
+pub fn run_file(path: &Path) -> Result<()> {
+    info!(target: "cli", path = %path.display(), "running file");
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}.", path.display()))?;
+
+    let mut sources = SourceMap::new();
+    let file = sources.add(path.display().to_string(), source);
+    if !run_source(&mut Interpreter::new(), &sources, file) {
+        bail!("{} has errors.", path.display());
+    }
+    Ok(())
+}
+
+/// Scans, parses and runs `file`, printing any problems to stderr with the source line they
+/// are on. Returns whether it had no errors.
+fn run_source(interpreter: &mut Interpreter, sources: &SourceMap, file: FileId) -> bool {
+    let mut scanner = Scanner::with_file(sources.file(file).source.clone(), file);
+    let tokens = scanner.scan_tokens();
+    for warning in &scanner.warnings {
+        let message = format!("Warning: {}", warning.message);
+        eprintln!("{}", sources.render(warning.file, warning.span, &message));
+    }
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            for error in errors {
+                let message = format!("Error: {}", error.message);
+                eprintln!("{}", sources.render(error.file, error.span, &message));
+            }
+            return false;
+        }
+    };
+    debug!(target: "cli", tokens = tokens.len(), "scanned");
+
+    let program = match LoxParser::new(tokens).parse() {
+        Ok(program) => program,
+        Err(errors) => {
+            for error in errors {
+                let message = format!("Error at {}: {}", error.place(), error.message);
+                eprintln!("{}", sources.render(error.file(), error.span(), &message));
+            }
+            return false;
+        }
+    };
+    debug!(target: "parser", ast = %Astprinter.print_program(&program), "parsed");
+
+    if let Err(error) = interpreter.interpret(&program) {
+        let message = format!("Error: {}", error.message);
+        eprintln!("{}", sources.render(error.file, error.span, &message));
+        return false;
+    }
+    true
+}
+
 pub fn interactive_session() {
     println!("Entering interactive mode. Type '#quit' to exit.");
+    let mut interpreter = Interpreter::new();
+    // Every line stays loaded, since functions declared on one can fail when called on another.
+    let mut sources = SourceMap::new();
     loop {
         // Display a prompt
         print!("> ");
@@ -53,6 +143,7 @@ pub fn interactive_session() {
             .read_line(&mut input)
             .expect("Failed to read line");
         let input = input.trim(); // Remove any trailing newline or spaces
+        debug!(target: "cli", input, "read line");
 
         // Check if the user typed "#quit"
         if input == "#quit" {
@@ -60,7 +151,9 @@ pub fn interactive_session() {
             break;
         }
 
-        // Echo back the input
-        println!("{}", input);
+        if !input.is_empty() {
+            let file = sources.add("<repl>", input);
+            run_source(&mut interpreter, &sources, file);
+        }
     }
 }
@@ -0,0 +1,105 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// The parts of the interpreter that emit trace events. Each is also the `target` its events
+/// are recorded under.
+pub const TARGETS: [&str; 3] = ["scanner", "parser", "cli"];
+
+/// Builds the filter for a `--trace` spec: a comma-separated list of targets, each optionally
+/// followed by `=level`, or `all` for every target. Targets without a level use `level`.
+pub fn filter(spec: &str, level: LevelFilter) -> Result<Targets> {
+    let mut filter = Targets::new();
+    for entry in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (name, level) = match entry.split_once('=') {
+            Some((name, entry_level)) => (
+                name,
+                entry_level
+                    .parse::<LevelFilter>()
+                    .map_err(|_| anyhow!("Unknown trace level '{}'.", entry_level))?,
+            ),
+            None => (entry, level),
+        };
+
+        if name == "all" {
+            filter = filter.with_targets(TARGETS.map(|target| (target, level)));
+        } else if TARGETS.contains(&name) {
+            filter = filter.with_target(name, level);
+        } else {
+            return Err(anyhow!(
+                "Unknown trace target '{}'. Expected one of: all, {}.",
+                name,
+                TARGETS.join(", ")
+            ));
+        }
+    }
+    Ok(filter)
+}
+
+/// Installs the global subscriber. Traces go to stderr, or to `file` when one is given, so
+/// they never mix with the program's own output.
+pub fn init(spec: &str, level: LevelFilter, file: Option<&Path>) -> Result<()> {
+    let filter = filter(spec, level)?;
+    let (writer, ansi) = match file {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Could not create trace file {}.", path.display()))?;
+            (BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::TRACE)
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .without_time()
+        .finish()
+        .with(filter);
+    tracing::subscriber::set_global_default(subscriber)
+        .context("A trace subscriber is already installed.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_spec() {
+        let targets = filter("scanner, parser=debug", LevelFilter::INFO).unwrap();
+        assert!(targets.would_enable("scanner", &tracing::Level::INFO));
+        assert!(!targets.would_enable("scanner", &tracing::Level::DEBUG));
+        assert!(targets.would_enable("parser", &tracing::Level::DEBUG));
+        assert!(!targets.would_enable("cli", &tracing::Level::ERROR));
+
+        let all = filter("all", LevelFilter::TRACE).unwrap();
+        assert!(TARGETS
+            .iter()
+            .all(|target| all.would_enable(target, &tracing::Level::TRACE)));
+    }
+
+    #[test]
+    fn test_unknown_trace_targets_and_levels() {
+        assert_eq!(
+            filter("scaner", LevelFilter::TRACE)
+                .unwrap_err()
+                .to_string(),
+            "Unknown trace target 'scaner'. Expected one of: all, scanner, parser, cli."
+        );
+        assert_eq!(
+            filter("parser=loud", LevelFilter::TRACE)
+                .unwrap_err()
+                .to_string(),
+            "Unknown trace level 'loud'."
+        );
+    }
+}
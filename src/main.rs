@@ -1,8 +1,5 @@
-mod loxcli;
-mod parser;
-mod scanner;
+use lox::loxcli::cli::run_command;
 
-use crate::loxcli::cli::run_command;
-fn main() {
-    run_command();
+fn main() -> anyhow::Result<()> {
+    run_command()
 }
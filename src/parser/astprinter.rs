@@ -1,57 +1,277 @@
-use crate::{
-    parser::expressions::Visitor,
-    scanner::tokentype::{Literal, TokenType},
+use crate::parser::expressions::{
+    AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr, IndexSetExpr,
+    InterpolationExpr, LambdaExpr, ListExpr, LiteralExpr, LiteralValue, LogicalExpr,
+    LogicalOperator, MapExpr, SetExpr, SliceExpr, SuperExpr, TernaryExpr, ThisExpr, UnaryExpr,
+    VariableExpr, Visitor,
 };
-
-use either::Either::{Left, Right};
+use crate::parser::statements::{
+    BlockStmt, BreakStmt, ClassStmt, ContinueStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt,
+    Program, ReturnStmt, StmtVisitor, VarStmt, WhileStmt,
+};
+use std::rc::Rc;
 
 pub struct Astprinter;
 
-impl Visitor for Astprinter {
-    fn visit_literalexpr(&mut self, expr: &super::expressions::LiteralExpr) -> String {
+impl Astprinter {
+    /// One line per top-level statement.
+    pub fn print_program(&mut self, program: &Program) -> String {
+        let statements: Vec<String> = program
+            .statements
+            .iter()
+            .map(|stmt| stmt.accept(self))
+            .collect();
+        statements.join("\n")
+    }
+}
+
+impl Visitor<String> for Astprinter {
+    fn visit_literalexpr(&mut self, expr: &LiteralExpr) -> String {
         match &expr.value {
-            Left(c) => match c {
-                Literal::Str(s) => s.clone(),
-                Literal::Number(s) => s.to_string(),
-                Literal::Identifier(s) => s.clone(),
-            },
-            Right(c) => format!("{:?}", c),
+            LiteralValue::Str(s) => s.clone(),
+            LiteralValue::Number(n) => n.to_string(),
+            LiteralValue::Bool(b) => b.to_string(),
+            LiteralValue::Nil => String::from("nil"),
         }
     }
 
-    fn visit_gropingexpr(&mut self, expr: &super::expressions::GroupingExpr) -> String {
+    fn visit_groupingexpr(&mut self, expr: &GroupingExpr) -> String {
         format!("(group {})", expr.expression.accept(self))
     }
 
-    fn visit_unaryexpr(&mut self, expr: &super::expressions::UnaryExpr) -> String {
+    fn visit_unaryexpr(&mut self, expr: &UnaryExpr) -> String {
         let operation = expr.operator.to_token().to_string();
         let expression = expr.expression.accept(self); // Visita la expresión interna
         format!("({} {})", operation, expression)
     }
 
-    fn visit_binaryexpr(&mut self, expr: &super::expressions::BinaryExpr) -> String {
+    fn visit_binaryexpr(&mut self, expr: &BinaryExpr) -> String {
         let left = expr.left.accept(self);
-        let operator = expr.operator.to_string();
+        let operator = expr.operator.to_token().to_string();
         let right = expr.right.accept(self);
         format!("({} {} {})", left, operator, right)
     }
+
+    fn visit_interpolationexpr(&mut self, expr: &InterpolationExpr) -> String {
+        let parts: Vec<String> = expr.parts.iter().map(|part| part.accept(self)).collect();
+        format!("(interpolate {})", parts.join(" "))
+    }
+
+    fn visit_variableexpr(&mut self, expr: &VariableExpr) -> String {
+        expr.name.symbol.to_string()
+    }
+
+    fn visit_assignexpr(&mut self, expr: &AssignExpr) -> String {
+        format!("(= {} {})", expr.name.symbol, expr.value.accept(self))
+    }
+
+    fn visit_callexpr(&mut self, expr: &CallExpr) -> String {
+        let mut parts = vec![expr.callee.accept(self)];
+        parts.extend(expr.arguments.iter().map(|argument| argument.accept(self)));
+        format!("(call {})", parts.join(" "))
+    }
+
+    fn visit_getexpr(&mut self, expr: &GetExpr) -> String {
+        format!("(. {} {})", expr.object.accept(self), expr.name.symbol)
+    }
+
+    fn visit_setexpr(&mut self, expr: &SetExpr) -> String {
+        format!(
+            "(.= {} {} {})",
+            expr.object.accept(self),
+            expr.name.symbol,
+            expr.value.accept(self)
+        )
+    }
+
+    fn visit_thisexpr(&mut self, _: &ThisExpr) -> String {
+        String::from("this")
+    }
+
+    fn visit_superexpr(&mut self, expr: &SuperExpr) -> String {
+        format!("(super {})", expr.method.symbol)
+    }
+
+    fn visit_lambdaexpr(&mut self, expr: &LambdaExpr) -> String {
+        let declaration = &expr.declaration;
+        let params: Vec<String> = declaration
+            .params
+            .iter()
+            .map(|p| p.symbol.to_string())
+            .collect();
+        let body: Vec<String> = declaration.body.iter().map(|s| s.accept(self)).collect();
+        format!("(lambda ({}) {})", params.join(" "), body.join(" "))
+    }
+
+    fn visit_listexpr(&mut self, expr: &ListExpr) -> String {
+        let mut parts = vec![String::from("list")];
+        parts.extend(expr.elements.iter().map(|element| element.accept(self)));
+        format!("({})", parts.join(" "))
+    }
+
+    fn visit_indexexpr(&mut self, expr: &IndexExpr) -> String {
+        format!(
+            "(index {} {})",
+            expr.object.accept(self),
+            expr.index.accept(self)
+        )
+    }
+
+    fn visit_indexsetexpr(&mut self, expr: &IndexSetExpr) -> String {
+        format!(
+            "(index= {} {} {})",
+            expr.object.accept(self),
+            expr.index.accept(self),
+            expr.value.accept(self)
+        )
+    }
+
+    /// A missing bound prints as `_`.
+    fn visit_sliceexpr(&mut self, expr: &SliceExpr) -> String {
+        let mut bound = |bound: &Option<Box<Expr>>| match bound {
+            Some(bound) => bound.accept(self),
+            None => String::from("_"),
+        };
+        let (start, end) = (bound(&expr.start), bound(&expr.end));
+        format!("(slice {} {} {})", expr.object.accept(self), start, end)
+    }
+
+    fn visit_mapexpr(&mut self, expr: &MapExpr) -> String {
+        let mut parts = vec![String::from("map")];
+        for (key, value) in &expr.entries {
+            parts.push(format!("({} {})", key.accept(self), value.accept(self)));
+        }
+        format!("({})", parts.join(" "))
+    }
+
+    fn visit_ternaryexpr(&mut self, expr: &TernaryExpr) -> String {
+        format!(
+            "({} ? {} : {})",
+            expr.condition.accept(self),
+            expr.then_branch.accept(self),
+            expr.else_branch.accept(self)
+        )
+    }
+
+    fn visit_logicalexpr(&mut self, expr: &LogicalExpr) -> String {
+        let operator = match expr.operator {
+            LogicalOperator::And => "and",
+            LogicalOperator::Or => "or",
+        };
+        format!(
+            "({} {} {})",
+            expr.left.accept(self),
+            operator,
+            expr.right.accept(self)
+        )
+    }
+}
+
+impl StmtVisitor<String> for Astprinter {
+    fn visit_expressionstmt(&mut self, stmt: &ExpressionStmt) -> String {
+        format!("(expr {})", stmt.expression.accept(self))
+    }
+
+    fn visit_printstmt(&mut self, stmt: &PrintStmt) -> String {
+        format!("(print {})", stmt.expression.accept(self))
+    }
+
+    fn visit_varstmt(&mut self, stmt: &VarStmt) -> String {
+        match &stmt.initializer {
+            Some(initializer) => format!("(var {} {})", stmt.name.symbol, initializer.accept(self)),
+            None => format!("(var {})", stmt.name.symbol),
+        }
+    }
+
+    fn visit_blockstmt(&mut self, stmt: &BlockStmt) -> String {
+        let statements: Vec<String> = stmt.statements.iter().map(|s| s.accept(self)).collect();
+        format!("(block {})", statements.join(" "))
+    }
+
+    fn visit_ifstmt(&mut self, stmt: &IfStmt) -> String {
+        let condition = stmt.condition.accept(self);
+        let then_branch = stmt.then_branch.accept(self);
+        match &stmt.else_branch {
+            Some(else_branch) => format!(
+                "(if {} {} {})",
+                condition,
+                then_branch,
+                else_branch.accept(self)
+            ),
+            None => format!("(if {} {})", condition, then_branch),
+        }
+    }
+
+    fn visit_functionstmt(&mut self, stmt: &Rc<FunctionStmt>) -> String {
+        let params: Vec<String> = stmt.params.iter().map(|p| p.symbol.to_string()).collect();
+        let body: Vec<String> = stmt.body.iter().map(|s| s.accept(self)).collect();
+        format!(
+            "(fun {} ({}) {})",
+            stmt.name.symbol,
+            params.join(" "),
+            body.join(" ")
+        )
+    }
+
+    fn visit_returnstmt(&mut self, stmt: &ReturnStmt) -> String {
+        match &stmt.value {
+            Some(value) => format!("(return {})", value.accept(self)),
+            None => String::from("(return)"),
+        }
+    }
+
+    fn visit_classstmt(&mut self, stmt: &ClassStmt) -> String {
+        let mut parts = vec![stmt.name.symbol.to_string()];
+        if let Some(superclass) = &stmt.superclass {
+            parts.push(format!("< {}", superclass.name.symbol));
+        }
+        parts.extend(
+            stmt.methods
+                .iter()
+                .map(|method| self.visit_functionstmt(method)),
+        );
+        format!("(class {})", parts.join(" "))
+    }
+
+    /// A `for` increment is printed after the body, and a label before the loop.
+    fn visit_whilestmt(&mut self, stmt: &WhileStmt) -> String {
+        let mut parts = vec![stmt.condition.accept(self), stmt.body.accept(self)];
+        if let Some(increment) = &stmt.increment {
+            parts.push(increment.accept(self));
+        }
+        let while_loop = format!("(while {})", parts.join(" "));
+        match &stmt.label {
+            Some(label) => format!("(label {} {})", label.symbol, while_loop),
+            None => while_loop,
+        }
+    }
+
+    fn visit_breakstmt(&mut self, stmt: &BreakStmt) -> String {
+        match &stmt.label {
+            Some(label) => format!("(break {})", label.symbol),
+            None => String::from("(break)"),
+        }
+    }
+
+    fn visit_continuestmt(&mut self, stmt: &ContinueStmt) -> String {
+        match &stmt.label {
+            Some(label) => format!("(continue {})", label.symbol),
+            None => String::from("(continue)"),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{
-        parser::expressions::{BinaryExpr, LiteralExpr, Visitor},
-        scanner::tokentype::{Literal, TokenType},
+    use crate::parser::expressions::{
+        BinaryExpr, Expr, LiteralExpr, LiteralValue, Location, Operator, Visitor,
     };
 
     use super::Astprinter;
 
     #[test]
     fn astprinter_literal_left() {
-        let literal = Literal::Str("hola".to_string());
-        let literal_expresion = LiteralExpr {
-            value: either::Either::Left(literal),
-        };
+        let literal = LiteralValue::Str("hola".to_string());
+        let literal_expresion = LiteralExpr { value: literal };
 
         let mut ast_printer = Astprinter {};
 
@@ -62,34 +282,29 @@ mod test {
 
     #[test]
     fn astprinter_literal_right() {
-        let literal = TokenType::False;
-        let literal_expresion = LiteralExpr {
-            value: either::Either::Right(literal),
-        };
+        let literal = LiteralValue::Bool(false);
+        let literal_expresion = LiteralExpr { value: literal };
 
         let mut ast_printer = Astprinter {};
 
         let val = ast_printer.visit_literalexpr(&literal_expresion);
 
         println!("{}", val);
-        assert_eq!(val, "False".to_string())
+        assert_eq!(val, "false".to_string())
     }
 
     #[test]
     fn astprinter_binary() {
-        let litertal1 = Literal::Number(64 as f64);
-        let literal1Expresion = LiteralExpr {
-            value: either::Either::Left(litertal1),
-        };
-        let litertal2 = Literal::Number(32 as f64);
-        let literal2Expresion = LiteralExpr {
-            value: either::Either::Left(litertal2),
-        };
+        let litertal1 = LiteralValue::Number(64.0);
+        let literal1_expresion = Expr::Literal(LiteralExpr { value: litertal1 });
+        let litertal2 = LiteralValue::Number(32.0);
+        let literal2_expresion = Expr::Literal(LiteralExpr { value: litertal2 });
 
         let binary_expr = BinaryExpr {
-            left: Box::new(literal1Expresion),
-            operator: TokenType::Minus,
-            right: Box::new(literal2Expresion),
+            left: Box::new(literal1_expresion),
+            operator: Operator::Minus,
+            right: Box::new(literal2_expresion),
+            location: Location::default(),
         };
         let mut ast_printer = Astprinter {};
 
@@ -0,0 +1,58 @@
+use std::fmt;
+
+use crate::scanner::{
+    source::{FileId, Span},
+    token::Token,
+    tokentype::TokenType,
+};
+
+/// A syntax error at `token`. `expected` lists the token types that would have been accepted
+/// there, and is empty when the problem isn't a missing token.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub token: Box<Token>,
+    pub expected: Vec<TokenType>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(token: Token, expected: Vec<TokenType>, message: impl Into<String>) -> Self {
+        ParseError {
+            token: Box::new(token),
+            expected,
+            message: message.into(),
+        }
+    }
+
+    pub fn file(&self) -> FileId {
+        self.token.file
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+
+    /// The token the error is at as shown in messages: its lexeme in quotes, or `end`.
+    pub fn place(&self) -> String {
+        if self.token.ty == TokenType::Eof {
+            String::from("end")
+        } else {
+            format!("'{}'", self.token.lexeme)
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error at {}: {}",
+            self.token.line,
+            self.token.col,
+            self.place(),
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
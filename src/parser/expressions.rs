@@ -1,11 +1,15 @@
 // This was implemented in the book as a way to understand grammar. Problem is this grammar is
 // ambigous. Im gonna leave at here with the AST printer wich use it and gona make a new one.
 
-use std::fmt::Debug;
+use std::rc::Rc;
 
-use either::Either;
-
-use crate::scanner::tokentype::{Literal, TokenType};
+use super::statements::FunctionStmt;
+use crate::scanner::{
+    source::{FileId, Span},
+    symbol::Symbol,
+    token::Token,
+    tokentype::{Literal, TokenType},
+};
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum UnaryOperator {
@@ -44,6 +48,8 @@ pub enum Operator {
     GreaterEqual,
     Less,
     LessEqual,
+    /// `a, b`: evaluates both and is the value of `b`.
+    Comma,
 }
 
 impl Operator {
@@ -61,6 +67,7 @@ impl Operator {
             TokenType::GreaterEqual => Some(Operator::GreaterEqual),
             TokenType::Less => Some(Operator::Less),
             TokenType::LessEqual => Some(Operator::LessEqual),
+            TokenType::Comma => Some(Operator::Comma),
             _ => None, // No es un operador
         }
     }
@@ -79,80 +86,477 @@ impl Operator {
             Operator::GreaterEqual => TokenType::GreaterEqual,
             Operator::Less => TokenType::Less,
             Operator::LessEqual => TokenType::LessEqual,
+            Operator::Comma => TokenType::Comma,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Binary(BinaryExpr),
+    Unary(UnaryExpr),
+    Grouping(GroupingExpr),
+    Literal(LiteralExpr),
+    Interpolation(InterpolationExpr),
+    Variable(VariableExpr),
+    Logical(LogicalExpr),
+    Assign(AssignExpr),
+    Call(CallExpr),
+    Get(GetExpr),
+    Set(SetExpr),
+    This(ThisExpr),
+    Super(SuperExpr),
+    Lambda(LambdaExpr),
+    List(ListExpr),
+    Index(IndexExpr),
+    IndexSet(IndexSetExpr),
+    Slice(SliceExpr),
+    Map(MapExpr),
+    Ternary(TernaryExpr),
+}
+
+impl Expr {
+    pub fn accept<R>(&self, visitor: &mut dyn Visitor<R>) -> R {
+        match self {
+            Expr::Binary(expr) => visitor.visit_binaryexpr(expr),
+            Expr::Unary(expr) => visitor.visit_unaryexpr(expr),
+            Expr::Grouping(expr) => visitor.visit_groupingexpr(expr),
+            Expr::Literal(expr) => visitor.visit_literalexpr(expr),
+            Expr::Interpolation(expr) => visitor.visit_interpolationexpr(expr),
+            Expr::Variable(expr) => visitor.visit_variableexpr(expr),
+            Expr::Logical(expr) => visitor.visit_logicalexpr(expr),
+            Expr::Assign(expr) => visitor.visit_assignexpr(expr),
+            Expr::Call(expr) => visitor.visit_callexpr(expr),
+            Expr::Get(expr) => visitor.visit_getexpr(expr),
+            Expr::Set(expr) => visitor.visit_setexpr(expr),
+            Expr::This(expr) => visitor.visit_thisexpr(expr),
+            Expr::Super(expr) => visitor.visit_superexpr(expr),
+            Expr::Lambda(expr) => visitor.visit_lambdaexpr(expr),
+            Expr::List(expr) => visitor.visit_listexpr(expr),
+            Expr::Index(expr) => visitor.visit_indexexpr(expr),
+            Expr::IndexSet(expr) => visitor.visit_indexsetexpr(expr),
+            Expr::Slice(expr) => visitor.visit_sliceexpr(expr),
+            Expr::Map(expr) => visitor.visit_mapexpr(expr),
+            Expr::Ternary(expr) => visitor.visit_ternaryexpr(expr),
         }
     }
 }
 
-pub trait Expression: Debug {
-    fn accept(&self, visitor: &mut dyn Visitor) -> String;
+/// A pass over expressions that produces an `R` for each one: a printer, an evaluator, a
+/// resolver.
+pub trait Visitor<R> {
+    fn visit_binaryexpr(&mut self, expr: &BinaryExpr) -> R;
+    fn visit_unaryexpr(&mut self, expr: &UnaryExpr) -> R;
+    fn visit_groupingexpr(&mut self, expr: &GroupingExpr) -> R;
+    fn visit_literalexpr(&mut self, expr: &LiteralExpr) -> R;
+    fn visit_interpolationexpr(&mut self, expr: &InterpolationExpr) -> R;
+    fn visit_variableexpr(&mut self, expr: &VariableExpr) -> R;
+    fn visit_logicalexpr(&mut self, expr: &LogicalExpr) -> R;
+    fn visit_assignexpr(&mut self, expr: &AssignExpr) -> R;
+    fn visit_callexpr(&mut self, expr: &CallExpr) -> R;
+    fn visit_getexpr(&mut self, expr: &GetExpr) -> R;
+    fn visit_setexpr(&mut self, expr: &SetExpr) -> R;
+    fn visit_thisexpr(&mut self, expr: &ThisExpr) -> R;
+    fn visit_superexpr(&mut self, expr: &SuperExpr) -> R;
+    fn visit_lambdaexpr(&mut self, expr: &LambdaExpr) -> R;
+    fn visit_listexpr(&mut self, expr: &ListExpr) -> R;
+    fn visit_indexexpr(&mut self, expr: &IndexExpr) -> R;
+    fn visit_indexsetexpr(&mut self, expr: &IndexSetExpr) -> R;
+    fn visit_sliceexpr(&mut self, expr: &SliceExpr) -> R;
+    fn visit_mapexpr(&mut self, expr: &MapExpr) -> R;
+    fn visit_ternaryexpr(&mut self, expr: &TernaryExpr) -> R;
 }
 
-pub trait Visitor {
-    fn visit_binaryexpr(&mut self, expr: &BinaryExpr) -> String;
-    fn visit_unaryexpr(&mut self, expr: &UnaryExpr) -> String;
-    fn visit_gropingexpr(&mut self, expr: &GroupingExpr) -> String;
-    fn visit_literalexpr(&mut self, expr: &LiteralExpr) -> String;
+/// Where something was written in the source, kept in the AST for runtime errors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub col: u32,
+    pub file: FileId,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+impl Location {
+    pub fn of(token: &Token) -> Location {
+        Location {
+            line: token.line,
+            col: token.col,
+            file: token.file,
+            span: token.span,
+        }
+    }
+}
+
+/// An identifier as written in the source, with where it was written for error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Name {
+    pub symbol: Symbol,
+    pub location: Location,
+}
+
+impl Name {
+    /// The name of an identifier token.
+    pub fn from_token(token: &Token) -> Option<Name> {
+        match token.literal {
+            Some(Literal::Identifier(symbol)) => Some(Name {
+                symbol,
+                location: Location::of(token),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct BinaryExpr {
-    pub left: Box<dyn Expression>,
-    pub operator: TokenType,
-    pub right: Box<dyn Expression>,
+    pub left: Box<Expr>,
+    pub operator: Operator,
+    pub right: Box<Expr>,
+    /// Where the operator is.
+    pub location: Location,
 }
 
 impl BinaryExpr {
-    pub fn new(left: Box<dyn Expression>, operator: TokenType, right: Box<dyn Expression>) -> Self {
+    pub fn new(left: Expr, operator: Operator, right: Expr, location: Location) -> Self {
         BinaryExpr {
-            left,
-            right,
+            left: Box::new(left),
+            right: Box::new(right),
             operator,
+            location,
         }
     }
 }
 
-impl Expression for BinaryExpr {
-    fn accept(&self, visitor: &mut dyn Visitor) -> String {
-        visitor.visit_binaryexpr(self)
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UnaryExpr {
     pub operator: UnaryOperator,
-    pub expression: Box<dyn Expression>,
+    pub expression: Box<Expr>,
+    pub location: Location,
 }
 
-impl Expression for UnaryExpr {
-    fn accept(&self, visitor: &mut dyn Visitor) -> String {
-        visitor.visit_unaryexpr(self)
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupingExpr {
+    pub expression: Box<Expr>,
 }
 
-#[derive(Debug)]
-pub struct GroupingExpr {
-    pub expression: Box<dyn Expression>,
+/// The value of a literal written in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralValue {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    Nil,
 }
 
-impl Expression for GroupingExpr {
-    fn accept(&self, visitor: &mut dyn Visitor) -> String {
-        visitor.visit_gropingexpr(self)
+impl LiteralValue {
+    /// The value of a literal token: a number, a string or string piece, `true`, `false` or
+    /// `nil`.
+    pub fn from_token(ty: TokenType, literal: Option<&Literal>) -> Option<LiteralValue> {
+        match (ty, literal) {
+            (TokenType::True, _) => Some(LiteralValue::Bool(true)),
+            (TokenType::False, _) => Some(LiteralValue::Bool(false)),
+            (TokenType::Nil, _) => Some(LiteralValue::Nil),
+            (_, Some(Literal::Number(n))) => Some(LiteralValue::Number(*n)),
+            (_, Some(Literal::Str(s))) => Some(LiteralValue::Str(s.clone())),
+            _ => None,
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LiteralExpr {
-    pub value: Either<Literal, TokenType>,
+    pub value: LiteralValue,
 }
 
 impl LiteralExpr {
-    pub fn new(literal: Either<Literal, TokenType>) -> Self {
-        LiteralExpr { value: literal }
+    pub fn new(value: LiteralValue) -> Self {
+        LiteralExpr { value }
     }
 }
 
-impl Expression for LiteralExpr {
-    fn accept(&self, visitor: &mut dyn Visitor) -> String {
-        visitor.visit_literalexpr(self)
+/// An interpolated string such as `"a ${b} c"`: the string pieces and the embedded expressions
+/// in source order, to be converted to strings and concatenated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpolationExpr {
+    pub parts: Vec<Expr>,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum LogicalOperator {
+    And,
+    Or,
+}
+
+/// `left and right` or `left or right`. The right side is only evaluated when the left one
+/// doesn't already decide the result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalExpr {
+    pub left: Box<Expr>,
+    pub operator: LogicalOperator,
+    pub right: Box<Expr>,
+}
+
+/// A read of the variable `name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableExpr {
+    pub name: Name,
+}
+
+/// `callee(arguments)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallExpr {
+    pub callee: Box<Expr>,
+    pub arguments: Vec<Expr>,
+    /// Where the closing `)` is.
+    pub location: Location,
+}
+
+/// `name = value`, which evaluates to the assigned value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssignExpr {
+    pub name: Name,
+    pub value: Box<Expr>,
+}
+
+/// `object.name`, reading a field or method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetExpr {
+    pub object: Box<Expr>,
+    pub name: Name,
+}
+
+/// `object.name = value`, which evaluates to the assigned value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetExpr {
+    pub object: Box<Expr>,
+    pub name: Name,
+    pub value: Box<Expr>,
+}
+
+/// `this`, looked up like a variable called `this`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThisExpr {
+    pub keyword: Name,
+}
+
+/// `super.method`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuperExpr {
+    pub keyword: Name,
+    pub method: Name,
+}
+
+/// An anonymous function, `fun (params) { body }` or `(params) => body`. The declaration is
+/// named `lambda`, at the token the function starts at; an arrow body is a single `return`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LambdaExpr {
+    pub declaration: Rc<FunctionStmt>,
+}
+
+/// `[elements]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListExpr {
+    pub elements: Vec<Expr>,
+}
+
+/// `object[index]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexExpr {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    /// Where the `[` is.
+    pub location: Location,
+}
+
+/// `object[index] = value`, which evaluates to the assigned value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSetExpr {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+    /// Where the `[` is.
+    pub location: Location,
+}
+
+/// `object[start:end]`, where either bound can be left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceExpr {
+    pub object: Box<Expr>,
+    pub start: Option<Box<Expr>>,
+    pub end: Option<Box<Expr>>,
+    /// Where the `[` is.
+    pub location: Location,
+}
+
+/// `{key: value, ...}`, in expression position. Keys are evaluated, so `{a: 1}` uses the
+/// value of `a`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapExpr {
+    pub entries: Vec<(Expr, Expr)>,
+    /// Where the `{` is.
+    pub location: Location,
+}
+
+/// `condition ? then_branch : else_branch`, which only evaluates the branch it picks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TernaryExpr {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::Parser;
+    use crate::scanner::scanner::Scanner;
+
+    /// Evaluates arithmetic on numbers, to check visitors can return something besides text.
+    struct Arithmetic;
+
+    impl Visitor<f64> for Arithmetic {
+        fn visit_binaryexpr(&mut self, expr: &BinaryExpr) -> f64 {
+            let (left, right) = (expr.left.accept(self), expr.right.accept(self));
+            match expr.operator {
+                Operator::Plus => left + right,
+                Operator::Minus => left - right,
+                Operator::Star => left * right,
+                Operator::Slash => left / right,
+                Operator::Comma => right,
+                operator => panic!("unexpected operator {:?}", operator),
+            }
+        }
+
+        fn visit_unaryexpr(&mut self, expr: &UnaryExpr) -> f64 {
+            -expr.expression.accept(self)
+        }
+
+        fn visit_groupingexpr(&mut self, expr: &GroupingExpr) -> f64 {
+            expr.expression.accept(self)
+        }
+
+        fn visit_literalexpr(&mut self, expr: &LiteralExpr) -> f64 {
+            match expr.value {
+                LiteralValue::Number(n) => n,
+                ref value => panic!("unexpected literal {:?}", value),
+            }
+        }
+
+        fn visit_interpolationexpr(&mut self, _: &InterpolationExpr) -> f64 {
+            panic!("unexpected interpolation")
+        }
+
+        fn visit_variableexpr(&mut self, expr: &VariableExpr) -> f64 {
+            panic!("unexpected variable {}", expr.name.symbol)
+        }
+
+        fn visit_logicalexpr(&mut self, _: &LogicalExpr) -> f64 {
+            panic!("unexpected logical operator")
+        }
+
+        fn visit_assignexpr(&mut self, expr: &AssignExpr) -> f64 {
+            panic!("unexpected assignment to {}", expr.name.symbol)
+        }
+
+        fn visit_callexpr(&mut self, _: &CallExpr) -> f64 {
+            panic!("unexpected call")
+        }
+
+        fn visit_getexpr(&mut self, _: &GetExpr) -> f64 {
+            panic!("unexpected property")
+        }
+
+        fn visit_setexpr(&mut self, _: &SetExpr) -> f64 {
+            panic!("unexpected property")
+        }
+
+        fn visit_thisexpr(&mut self, _: &ThisExpr) -> f64 {
+            panic!("unexpected this")
+        }
+
+        fn visit_superexpr(&mut self, _: &SuperExpr) -> f64 {
+            panic!("unexpected super")
+        }
+
+        fn visit_lambdaexpr(&mut self, _: &LambdaExpr) -> f64 {
+            panic!("unexpected function")
+        }
+
+        fn visit_listexpr(&mut self, _: &ListExpr) -> f64 {
+            panic!("unexpected list")
+        }
+
+        fn visit_indexexpr(&mut self, _: &IndexExpr) -> f64 {
+            panic!("unexpected index")
+        }
+
+        fn visit_indexsetexpr(&mut self, _: &IndexSetExpr) -> f64 {
+            panic!("unexpected index")
+        }
+
+        fn visit_sliceexpr(&mut self, _: &SliceExpr) -> f64 {
+            panic!("unexpected slice")
+        }
+
+        fn visit_mapexpr(&mut self, _: &MapExpr) -> f64 {
+            panic!("unexpected map")
+        }
+
+        fn visit_ternaryexpr(&mut self, expr: &TernaryExpr) -> f64 {
+            if expr.condition.accept(self) != 0.0 {
+                expr.then_branch.accept(self)
+            } else {
+                expr.else_branch.accept(self)
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_with_non_string_result() {
+        let tokens = Scanner::new(String::from("-(1 + 2) * 4 / 2"))
+            .scan_tokens()
+            .unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+
+        assert_eq!(expression.accept(&mut Arithmetic), -6.0);
+    }
+
+    #[test]
+    fn test_ternary_is_right_associative() {
+        let tokens = Scanner::new(String::from("0 ? 1 : 0 ? 2 : 3, 4"))
+            .scan_tokens()
+            .unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+        let Expr::Binary(comma) = &expression else {
+            panic!("expected a comma expression, got {:?}", expression);
+        };
+        assert_eq!(comma.operator, Operator::Comma);
+        assert_eq!(comma.left.accept(&mut Arithmetic), 3.0);
+        assert_eq!(expression.accept(&mut Arithmetic), 4.0);
+    }
+
+    #[test]
+    fn test_binary_expressions_store_operators() {
+        let tokens = Scanner::new(String::from("1 <= 2")).scan_tokens().unwrap();
+        let expression = Parser::new(tokens).parse_expression().unwrap();
+
+        assert_eq!(
+            expression,
+            Expr::Binary(BinaryExpr::new(
+                Expr::Literal(LiteralExpr::new(LiteralValue::Number(1.0))),
+                Operator::LessEqual,
+                Expr::Literal(LiteralExpr::new(LiteralValue::Number(2.0))),
+                Location {
+                    line: 1,
+                    col: 3,
+                    file: FileId::default(),
+                    span: Span::new(2, 4),
+                },
+            ))
+        );
     }
 }
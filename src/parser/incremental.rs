@@ -0,0 +1,451 @@
+use std::rc::Rc;
+
+use super::error::ParseError;
+use super::expressions::{Expr, Location, Name};
+use super::parser::Parser;
+use super::statements::{FunctionStmt, Program, Stmt};
+use crate::scanner::{
+    error::ScanError,
+    incremental::{relex_shifted, Shift, TextEdit},
+    token::Token,
+    tokentype::TokenType,
+};
+
+/// Tokens handed to the parser at first when reparsing, past the ones scanned again.
+const MIN_WINDOW: usize = 64;
+
+/// Why an edited source has no program.
+#[derive(Debug)]
+pub enum EditError {
+    Scan(Vec<ScanError>),
+    Parse(Vec<ParseError>),
+}
+
+/// Applies `edit` to `source`, brings `tokens` up to date with `relex`, and gives back the
+/// program of the new source, which is exactly what `Parser::parse` would give for it.
+/// `program` must be the successful parse of the old `tokens`.
+///
+/// Only the top-level statements around the tokens scanned again are parsed again. The ones
+/// before them are kept as they are, and so are the ones after them, moved to where their
+/// tokens are now, once the new statements line up with one of them. A statement is only kept
+/// if the token after it is untouched too, since an `if` looks at it for an `else`. If the
+/// edit leaves a syntax error, the whole source is parsed so the errors are the same ones a
+/// full parse reports.
+pub fn reparse(
+    program: Program,
+    source: &mut String,
+    tokens: &mut Vec<Token>,
+    edit: &TextEdit,
+) -> Result<Program, EditError> {
+    let old_len = tokens.len();
+    let (rescanned, shift) = relex_shifted(source, tokens, edit).map_err(EditError::Scan)?;
+    let moved = tokens.len() as isize - old_len as isize;
+    let kept_from = (rescanned.end as isize - moved) as usize;
+
+    let mut reparsed = Program::default();
+    let mut after = Vec::new();
+    for (statement, range) in program.statements.into_iter().zip(program.ranges) {
+        if range.end < rescanned.start {
+            reparsed.push(statement, range);
+        } else if range.start >= kept_from {
+            let start = (range.start as isize + moved) as usize;
+            let end = (range.end as isize + moved) as usize;
+            after.push((statement, start..end));
+        }
+    }
+    let mut after = after.into_iter().peekable();
+
+    let mut position = reparsed.ranges.last().map_or(0, |range| range.end);
+    let mut window = rescanned.end.saturating_sub(position) + MIN_WINDOW;
+    'windows: loop {
+        // The parser only sees a window of the tokens, ending in an `Eof` of its own unless it
+        // reaches the real one. Statements that run into that `Eof` are tried again with a
+        // window twice as big.
+        let (start, end) = (position, (position + window).min(tokens.len()));
+        let truncated = end < tokens.len();
+        let mut parser = Parser::new(tokens[start..end].to_vec());
+        loop {
+            while after.next_if(|(_, range)| range.start < position).is_some() {}
+            if after
+                .peek()
+                .is_some_and(|(_, range)| range.start == position)
+                || tokens[position].ty == TokenType::Eof
+            {
+                break 'windows;
+            }
+
+            match parser.next_declaration() {
+                Some(Ok((statement, range))) if !truncated || range.end < end - start => {
+                    reparsed.push(statement, start + range.start..start + range.end);
+                    position = start + range.end;
+                }
+                Some(Err(_)) if !truncated => {
+                    return Parser::new(tokens.clone())
+                        .parse()
+                        .map_err(EditError::Parse);
+                }
+                _ => {
+                    window *= 2;
+                    continue 'windows;
+                }
+            }
+        }
+    }
+
+    for (mut statement, range) in after {
+        if !shift.is_empty() {
+            shift_stmt(&mut statement, &shift);
+        }
+        reparsed.push(statement, range);
+    }
+    Ok(reparsed)
+}
+
+fn shift_location(location: &mut Location, shift: &Shift) {
+    (location.line, location.col) = shift.position(location.line, location.col);
+    location.span = shift.span(location.span);
+}
+
+fn shift_name(name: &mut Name, shift: &Shift) {
+    shift_location(&mut name.location, shift);
+}
+
+fn shift_function(function: &mut Rc<FunctionStmt>, shift: &Shift) {
+    let function = Rc::make_mut(function);
+    shift_name(&mut function.name, shift);
+    for param in &mut function.params {
+        shift_name(param, shift);
+    }
+    for statement in &mut function.body {
+        shift_stmt(statement, shift);
+    }
+}
+
+/// Moves every location in `statement` by `shift`.
+fn shift_stmt(statement: &mut Stmt, shift: &Shift) {
+    match statement {
+        Stmt::Expression(stmt) => shift_expr(&mut stmt.expression, shift),
+        Stmt::Print(stmt) => {
+            shift_expr(&mut stmt.expression, shift);
+            shift_location(&mut stmt.location, shift);
+        }
+        Stmt::Var(stmt) => {
+            shift_name(&mut stmt.name, shift);
+            if let Some(initializer) = &mut stmt.initializer {
+                shift_expr(initializer, shift);
+            }
+        }
+        Stmt::Block(stmt) => {
+            for statement in &mut stmt.statements {
+                shift_stmt(statement, shift);
+            }
+        }
+        Stmt::If(stmt) => {
+            shift_expr(&mut stmt.condition, shift);
+            shift_stmt(&mut stmt.then_branch, shift);
+            if let Some(else_branch) = &mut stmt.else_branch {
+                shift_stmt(else_branch, shift);
+            }
+        }
+        Stmt::While(stmt) => {
+            shift_expr(&mut stmt.condition, shift);
+            shift_stmt(&mut stmt.body, shift);
+            if let Some(increment) = &mut stmt.increment {
+                shift_expr(increment, shift);
+            }
+            if let Some(label) = &mut stmt.label {
+                shift_name(label, shift);
+            }
+        }
+        Stmt::Function(function) => shift_function(function, shift),
+        Stmt::Return(stmt) => {
+            if let Some(value) = &mut stmt.value {
+                shift_expr(value, shift);
+            }
+            shift_location(&mut stmt.location, shift);
+        }
+        Stmt::Class(stmt) => {
+            shift_name(&mut stmt.name, shift);
+            if let Some(superclass) = &mut stmt.superclass {
+                shift_name(&mut superclass.name, shift);
+            }
+            for method in &mut stmt.methods {
+                shift_function(method, shift);
+            }
+        }
+        Stmt::Break(stmt) => {
+            if let Some(label) = &mut stmt.label {
+                shift_name(label, shift);
+            }
+            shift_location(&mut stmt.location, shift);
+        }
+        Stmt::Continue(stmt) => {
+            if let Some(label) = &mut stmt.label {
+                shift_name(label, shift);
+            }
+            shift_location(&mut stmt.location, shift);
+        }
+    }
+}
+
+fn shift_expr(expression: &mut Expr, shift: &Shift) {
+    match expression {
+        Expr::Binary(expr) => {
+            shift_expr(&mut expr.left, shift);
+            shift_expr(&mut expr.right, shift);
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::Unary(expr) => {
+            shift_expr(&mut expr.expression, shift);
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::Grouping(expr) => shift_expr(&mut expr.expression, shift),
+        Expr::Literal(_) => (),
+        Expr::Interpolation(expr) => {
+            for part in &mut expr.parts {
+                shift_expr(part, shift);
+            }
+        }
+        Expr::Variable(expr) => shift_name(&mut expr.name, shift),
+        Expr::Logical(expr) => {
+            shift_expr(&mut expr.left, shift);
+            shift_expr(&mut expr.right, shift);
+        }
+        Expr::Assign(expr) => {
+            shift_name(&mut expr.name, shift);
+            shift_expr(&mut expr.value, shift);
+        }
+        Expr::Call(expr) => {
+            shift_expr(&mut expr.callee, shift);
+            for argument in &mut expr.arguments {
+                shift_expr(argument, shift);
+            }
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::Get(expr) => {
+            shift_expr(&mut expr.object, shift);
+            shift_name(&mut expr.name, shift);
+        }
+        Expr::Set(expr) => {
+            shift_expr(&mut expr.object, shift);
+            shift_name(&mut expr.name, shift);
+            shift_expr(&mut expr.value, shift);
+        }
+        Expr::This(expr) => shift_name(&mut expr.keyword, shift),
+        Expr::Super(expr) => {
+            shift_name(&mut expr.keyword, shift);
+            shift_name(&mut expr.method, shift);
+        }
+        Expr::Lambda(expr) => shift_function(&mut expr.declaration, shift),
+        Expr::List(expr) => {
+            for element in &mut expr.elements {
+                shift_expr(element, shift);
+            }
+        }
+        Expr::Index(expr) => {
+            shift_expr(&mut expr.object, shift);
+            shift_expr(&mut expr.index, shift);
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::IndexSet(expr) => {
+            shift_expr(&mut expr.object, shift);
+            shift_expr(&mut expr.index, shift);
+            shift_expr(&mut expr.value, shift);
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::Slice(expr) => {
+            shift_expr(&mut expr.object, shift);
+            if let Some(start) = &mut expr.start {
+                shift_expr(start, shift);
+            }
+            if let Some(end) = &mut expr.end {
+                shift_expr(end, shift);
+            }
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::Map(expr) => {
+            for (key, value) in &mut expr.entries {
+                shift_expr(key, shift);
+                shift_expr(value, shift);
+            }
+            shift_location(&mut expr.location, shift);
+        }
+        Expr::Ternary(expr) => {
+            shift_expr(&mut expr.condition, shift);
+            shift_expr(&mut expr.then_branch, shift);
+            shift_expr(&mut expr.else_branch, shift);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::scanner::Scanner;
+
+    const SOURCE: &str = concat!(
+        "var a = 1;\n",
+        "fun f(x) {\n",
+        "  if (x > a) return x * 2;\n",
+        "  return -x;\n",
+        "}\n",
+        "class C < B {\n",
+        "  init() { this.v = super.m()[0]; }\n",
+        "}\n",
+        "if (a) print f(a); else { var l = [1, 2][0:1]; }\n",
+        "outer: for (var i = 0; i < 3; i = i + 1) { continue outer; }\n",
+        "print \"${a ? {\"k\": 1} : nil}\", (y) => y;\n",
+    );
+
+    fn parse(source: &str) -> Result<Program, Vec<ParseError>> {
+        Parser::new(Scanner::new(source.to_string()).scan_tokens().unwrap()).parse()
+    }
+
+    /// Reparses `source` after `edit` and checks the result against a full parse.
+    fn check_edit(source: &str, edit: TextEdit) -> Result<Program, EditError> {
+        let mut source = source.to_string();
+        let mut tokens = Scanner::new(source.clone()).scan_tokens().unwrap();
+        let program = Parser::new(tokens.clone()).parse().unwrap();
+
+        let result = reparse(program, &mut source, &mut tokens, &edit);
+        match (&result, Scanner::new(source.clone()).scan_tokens()) {
+            (Ok(program), Ok(expected)) => {
+                assert_eq!(format!("{:?}", tokens), format!("{:?}", expected));
+                assert_eq!(program, &parse(&source).unwrap(), "{:?}", edit);
+            }
+            (Err(EditError::Parse(errors)), Ok(_)) => assert_eq!(
+                format!("{:?}", errors),
+                format!("{:?}", parse(&source).unwrap_err()),
+                "{:?}",
+                edit
+            ),
+            (Err(EditError::Scan(errors)), Err(expected)) => assert_eq!(errors, &expected),
+            (result, expected) => panic!("{:?} gave {:?}, expected {:?}", edit, result, expected),
+        }
+        result
+    }
+
+    #[test]
+    fn test_reparse_matches_full_parse() {
+        let at = |text: &str| SOURCE.find(text).unwrap();
+        let edits = [
+            TextEdit::new(8..9, "2 + 3"),
+            TextEdit::new(0..0, "\n\nprint 0;"),
+            TextEdit::new(at("x * 2")..at("x * 2") + 1, "y"),
+            TextEdit::new(at(" else")..at(" else") + 5, ""),
+            TextEdit::new(at("f(a)")..at("f(a)") + 4, "f(a, a)"),
+            TextEdit::new(at("nil}")..at("nil}") + 3, "[a]"),
+            TextEdit::new(SOURCE.len()..SOURCE.len(), "var z;"),
+            TextEdit::new(0..SOURCE.len(), "print nil;"),
+        ];
+        for edit in edits {
+            check_edit(SOURCE, edit).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reparse_reports_the_same_errors_as_a_full_parse() {
+        let at = |text: &str| SOURCE.find(text).unwrap();
+        let edits = [
+            TextEdit::new(8..9, "+"),
+            TextEdit::new(0..0, "this;\n"),
+            TextEdit::new(at("outer:")..at("outer:"), "{"),
+        ];
+        for edit in edits {
+            let result = check_edit(SOURCE, edit);
+            assert!(matches!(result, Err(EditError::Parse(_))), "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_an_if_is_reparsed_when_an_else_is_added_after_it() {
+        let source = "if (a) print 1;\nprint 2;\n";
+        let program = check_edit(source, TextEdit::new(16..21, "else")).unwrap();
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_statements_longer_than_the_parser_window() {
+        let source = format!(
+            "print 0;\nfun f() {{\n{}}}\nprint 1;\n{}",
+            "  print 1 + 2;\n".repeat(100),
+            "if (a) print 3;\n".repeat(100)
+        );
+        check_edit(&source, TextEdit::new(7..7, " + 1")).unwrap();
+        let at = source.find("  print").unwrap();
+        check_edit(&source, TextEdit::new(at..at, "var a;")).unwrap();
+        let at = source.rfind("print 1;").unwrap();
+        check_edit(&source, TextEdit::new(at..at + 8, "{")).unwrap_err();
+        let at = source.find("print 3").unwrap();
+        check_edit(&source, TextEdit::new(at..at, "{ print 2; } else ")).unwrap();
+    }
+
+    #[test]
+    fn test_reparse_keeps_statements_away_from_the_edit() {
+        let mut source = "fun f() { return 1; }\n".repeat(50);
+        let mut tokens = Scanner::new(source.clone()).scan_tokens().unwrap();
+        let program = Parser::new(tokens.clone()).parse().unwrap();
+        let functions: Vec<_> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Stmt::Function(function) => Rc::downgrade(function),
+                _ => panic!("expected a function"),
+            })
+            .collect();
+
+        // Same length, so the functions after the edit don't move either.
+        let edit = TextEdit::new(25 * 22 + 17..25 * 22 + 18, "2");
+        let program = reparse(program, &mut source, &mut tokens, &edit).unwrap();
+
+        assert_eq!(program, parse(&source).unwrap());
+        for (i, (statement, old)) in program.statements.iter().zip(&functions).enumerate() {
+            let Stmt::Function(function) = statement else {
+                panic!("expected a function");
+            };
+            let kept = old.upgrade().is_some_and(|old| Rc::ptr_eq(&old, function));
+            assert_eq!(kept, i != 25, "function {}", i);
+        }
+    }
+
+    #[test]
+    fn test_random_edits() {
+        // Long enough that the parser only gets a window of the tokens.
+        let mut source = SOURCE.repeat(4);
+        let alphabet = [
+            "a", " ", "\n", ";", "{", "}", "(", ")", "if ", "else ", "print ", "var ", "1", "=",
+            ",", "?", ":", "\"", "[", "]", "fun ", "return ", "",
+        ];
+        let mut seed: u64 = 7;
+        let mut random = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound.max(1)
+        };
+
+        let mut reparsed = 0;
+        for _ in 0..600 {
+            let start = random(source.len() + 1);
+            let end = (start + random(4)).min(source.len());
+            let edit = TextEdit::new(start..end, alphabet[random(alphabet.len())]);
+
+            if check_edit(&source, edit.clone()).is_ok() {
+                reparsed += 1;
+            }
+            // Edits that break the source are undone, since a reparse starts from a program.
+            let before = source.clone();
+            source.replace_range(edit.range.clone(), &edit.replacement);
+            if !parses(&source) {
+                source = before;
+            }
+        }
+        assert!(reparsed > 50, "only {} edits reparsed", reparsed);
+    }
+
+    fn parses(source: &str) -> bool {
+        Scanner::new(source.to_string())
+            .scan_tokens()
+            .is_ok_and(|tokens| Parser::new(tokens).parse().is_ok())
+    }
+}
@@ -1,3 +1,7 @@
 pub mod astprinter;
+pub mod error;
 pub mod expressions;
+pub mod incremental;
+#[allow(clippy::module_inception)]
 pub mod parser;
+pub mod statements;
@@ -1,21 +1,103 @@
-use super::expressions::{BinaryExpr, Expression, LiteralExpr, UnaryExpr, UnaryOperator};
+use super::error::ParseError;
+use super::expressions::{
+    AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr, IndexSetExpr,
+    InterpolationExpr, LambdaExpr, ListExpr, LiteralExpr, LiteralValue, Location, LogicalExpr,
+    LogicalOperator, MapExpr, Name, Operator, SetExpr, SliceExpr, SuperExpr, TernaryExpr, ThisExpr,
+    UnaryExpr, UnaryOperator, VariableExpr,
+};
+use super::statements::{
+    BlockStmt, BreakStmt, ClassStmt, ContinueStmt, ExpressionStmt, FunctionStmt, IfStmt, PrintStmt,
+    Program, ReturnStmt, Stmt, VarStmt, WhileStmt,
+};
 use crate::scanner::{
+    source::{FileId, Span},
+    symbol::Symbol,
     token::Token,
-    tokentype::{Literal, TokenType},
+    tokentype::TokenType,
 };
-use either::Either::{self, Right};
 use std::borrow::Borrow;
+use std::ops::Range;
+use std::rc::Rc;
+use tracing::{debug, trace};
+
+type ParseResult = Result<Expr, ParseError>;
+
+/// A top-level declaration and the range of tokens it was parsed from.
+type Declaration = Result<(Stmt, Range<usize>), Vec<ParseError>>;
+
+/// Most parameters or arguments a function or call can have.
+const MAX_ARGUMENTS: usize = 255;
 
+/// How deep `nested` lets the parser go before it gives up on a statement: about 128 unary
+/// operators or nested `if`s, 64 nested blocks, or 42 nested parentheses, which take three
+/// levels each.
+/// An unoptimized build uses up to about 10 KiB of stack a level, so this stays well within
+/// the 2 MiB that Rust gives new threads.
+const MAX_DEPTH: usize = 128;
+
+/// Tokens that can start an expression.
+const EXPRESSION_START: [TokenType; 15] = [
+    TokenType::Minus,
+    TokenType::Bang,
+    TokenType::LeftParen,
+    TokenType::Number,
+    TokenType::String,
+    TokenType::InterpolationStart,
+    TokenType::True,
+    TokenType::False,
+    TokenType::Nil,
+    TokenType::Identifier,
+    TokenType::This,
+    TokenType::Super,
+    TokenType::Fun,
+    TokenType::LeftBracket,
+    TokenType::LeftBrace,
+];
+
+/// The kind of class body around the current token, for checking `this` and `super`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Recursive descent parser. A syntax error unwinds to the enclosing statement as an `Err`,
+/// and `synchronize` skips ahead to where the next statement starts so the rest of the source
+/// is still checked. Errors the parser can step over without losing its place, like a binary
+/// operator with no left operand, are collected in `errors` and parsing carries on.
+///
+/// `depth` counts the calls of `nested` the parser is in, and `too_deep` is set once it went
+/// past `MAX_DEPTH`; that error is not recovered from inside the statement, which is skipped
+/// as a whole instead.
+///
+/// `function_depth` counts the function bodies around the current token, `loops` holds the
+/// labels of the loops around it in the current function, and `in_initializer` and
+/// `class_kind` say whether it is in an `init` method or a class, so code that only makes
+/// sense in some places can be rejected.
 #[derive(Debug)]
-struct Parser {
+pub struct Parser {
     current: usize,
     tokens: Vec<Token>,
+    errors: Vec<ParseError>,
+    depth: usize,
+    too_deep: bool,
+    function_depth: usize,
+    loops: Vec<Option<Symbol>>,
+    in_initializer: bool,
+    class_kind: ClassKind,
 }
 
 impl Parser {
     pub fn match_token(&mut self, tokens_to_check: Vec<TokenType>) -> bool {
         for token in tokens_to_check.iter() {
             if self.check(token) {
+                trace!(
+                    target: "parser",
+                    ty = %token,
+                    line = self.tokens[self.current].line,
+                    "consumed token"
+                );
                 self.current += 1;
                 return true;
             }
@@ -34,148 +116,1542 @@ impl Parser {
         }
     }
 
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { current: 0, tokens }
+    /// `tokens` should end with `Eof`, as a scan does; one is added if it doesn't.
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        if tokens.last().is_none_or(|token| token.ty != TokenType::Eof) {
+            let (span, line, col) = tokens.last().map_or((Span::default(), 1, 1), |token| {
+                (
+                    Span::new(token.span.end, token.span.end),
+                    token.line,
+                    token.col,
+                )
+            });
+            tokens.push(Token::new(
+                TokenType::Eof,
+                "".into(),
+                None,
+                tokens.last().map_or(FileId::default(), |token| token.file),
+                span,
+                line,
+                col,
+            ));
+        }
+        Parser {
+            current: 0,
+            tokens,
+            errors: Vec::new(),
+            depth: 0,
+            too_deep: false,
+            function_depth: 0,
+            loops: Vec::new(),
+            in_initializer: false,
+            class_kind: ClassKind::None,
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    /// Whether the token after the next one is a `token_type`.
+    fn check_next(&self, token_type: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.ty == token_type)
+    }
+
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().ty == TokenType::Eof
+    }
+
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    /// Consumes the next token if it is a `token_type`, or fails with `message`.
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
+        if self.check(&token_type) {
+            return Ok(self.advance());
+        }
+        Err(self.error(vec![token_type], message))
+    }
+
+    /// Records an error the parser can carry on from at `token`.
+    fn report(&mut self, token: Token, message: &str) {
+        self.errors.push(ParseError::new(token, vec![], message));
+    }
+
+    /// An error at the next token.
+    fn error(&self, expected: Vec<TokenType>, message: &str) -> ParseError {
+        ParseError::new(self.peek().clone(), expected, message)
+    }
+
+    /// Discards tokens until the end of the current statement: just past a `;`, or right
+    /// before a keyword that starts a new one.
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous().ty == TokenType::Semicolon {
+                return;
+            }
+            match self.peek().ty {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Runs `parse` one level deeper, or fails if that is past `MAX_DEPTH`, so deeply nested
+    /// source is an error rather than a stack overflow.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Parser) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth == MAX_DEPTH {
+            self.too_deep = true;
+            return Err(self.error(vec![], "Too much nesting."));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Discards the top-level statement starting at token `start`, counting brackets to find
+    /// its end: a `;` outside of them, or for statements that can end in a block, like `if`
+    /// or a function declaration, also a `}` closing the last of them with no `else` after it.
+    fn skip_statement(&mut self, start: usize) {
+        self.current = start;
+        let ends_in_block = match self.peek().ty {
+            TokenType::LeftBrace
+            | TokenType::Class
+            | TokenType::If
+            | TokenType::While
+            | TokenType::For => true,
+            TokenType::Fun => !self.check_next(TokenType::LeftParen),
+            TokenType::Identifier => self.check_next(TokenType::Colon),
+            _ => false,
+        };
+        let mut open = 0usize;
+        while !self.is_at_end() {
+            match self.advance().ty {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => open += 1,
+                TokenType::RightParen | TokenType::RightBracket => open = open.saturating_sub(1),
+                TokenType::RightBrace => {
+                    open = open.saturating_sub(1);
+                    if open == 0 && ends_in_block && !self.check(&TokenType::Else) {
+                        return;
+                    }
+                }
+                TokenType::Semicolon if open == 0 => return,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn expression(&mut self) -> ParseResult {
+        self.comma()
+    }
+
+    /// `a, b`, the loosest operator. Where commas separate things, like call arguments, each
+    /// one is parsed with `assignment` instead.
+    pub fn comma(&mut self) -> ParseResult {
+        let mut expr = self.assignment()?;
+        while self.match_token(vec![TokenType::Comma]) {
+            let location = Location::of(self.previous());
+            let right = self.assignment()?;
+            expr = Expr::Binary(BinaryExpr::new(expr, Operator::Comma, right, location));
+        }
+        Ok(expr)
+    }
+
+    /// Assignment is right-associative, and its target is parsed as an ordinary expression
+    /// first and only then checked to be something that can be assigned to.
+    pub fn assignment(&mut self) -> ParseResult {
+        self.nested(|parser| {
+            let expr = parser.ternary()?;
+            if !parser.match_token(vec![TokenType::Equal]) {
+                return Ok(expr);
+            }
+
+            let equals = parser.previous().clone();
+            let value = parser.assignment()?;
+            match expr {
+                Expr::Variable(VariableExpr { name }) => Ok(Expr::Assign(AssignExpr {
+                    name,
+                    value: Box::new(value),
+                })),
+                Expr::Get(GetExpr { object, name }) => Ok(Expr::Set(SetExpr {
+                    object,
+                    name,
+                    value: Box::new(value),
+                })),
+                Expr::Index(IndexExpr {
+                    object,
+                    index,
+                    location,
+                }) => Ok(Expr::IndexSet(IndexSetExpr {
+                    object,
+                    index,
+                    value: Box::new(value),
+                    location,
+                })),
+                expr => {
+                    // The parser hasn't lost its place, so report it and carry on.
+                    parser.errors.push(ParseError::new(
+                        equals,
+                        vec![],
+                        "Invalid assignment target.",
+                    ));
+                    Ok(expr)
+                }
+            }
+        })
     }
 
-    pub fn expression(&mut self) -> Box<dyn Expression> {
-        self.equality()
+    /// `condition ? a : b`. It is right-associative, so the else branch can be another
+    /// conditional, and anything can go between `?` and `:`, as in C.
+    pub fn ternary(&mut self) -> ParseResult {
+        self.nested(|parser| {
+            let condition = parser.or()?;
+            if !parser.match_token(vec![TokenType::Question]) {
+                return Ok(condition);
+            }
+
+            let question = parser.previous().clone();
+            let then_branch = parser.expression()?;
+            if !parser.match_token(vec![TokenType::Colon]) {
+                return Err(ParseError::new(
+                    question,
+                    vec![TokenType::Colon],
+                    "Expected ':' to go with this '?'.",
+                ));
+            }
+            let else_branch = parser.ternary()?;
+            Ok(Expr::Ternary(TernaryExpr {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            }))
+        })
     }
 
-    pub fn equality(&mut self) -> Box<dyn Expression> {
-        let mut expr = self.comparison();
+    pub fn or(&mut self) -> ParseResult {
+        let mut expr = self.and()?;
+        while self.match_token(vec![TokenType::Or]) {
+            let right = self.and()?;
+            expr = Expr::Logical(LogicalExpr {
+                left: Box::new(expr),
+                operator: LogicalOperator::Or,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    pub fn and(&mut self) -> ParseResult {
+        let mut expr = self.equality()?;
+        while self.match_token(vec![TokenType::And]) {
+            let right = self.equality()?;
+            expr = Expr::Logical(LogicalExpr {
+                left: Box::new(expr),
+                operator: LogicalOperator::And,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    pub fn equality(&mut self) -> ParseResult {
+        let mut expr = self.comparison()?;
 
         while self.match_token(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.tokens[self.current - 1].clone().ty;
+            let location = Location::of(self.previous());
+            let operator = self.binary_operator()?;
 
-            let right = self.comparison();
-            expr = Box::new(BinaryExpr::new(expr, operator, right));
+            let right = self.comparison()?;
+            expr = Expr::Binary(BinaryExpr::new(expr, operator, right, location));
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn comparison(&mut self) -> Box<dyn Expression> {
-        let mut expr = self.term();
+    pub fn comparison(&mut self) -> ParseResult {
+        let mut expr = self.term()?;
         while self.match_token(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
-            let operator = self.tokens[self.current - 1].clone().ty;
-            let right = self.term();
-            expr = Box::new(BinaryExpr::new(expr, operator, right));
+            let location = Location::of(self.previous());
+            let operator = self.binary_operator()?;
+            let right = self.term()?;
+            expr = Expr::Binary(BinaryExpr::new(expr, operator, right, location));
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn term(&mut self) -> Box<dyn Expression> {
-        let mut expr = self.factor();
+    pub fn term(&mut self) -> ParseResult {
+        let mut expr = self.factor()?;
         while self.match_token(vec![TokenType::Minus, TokenType::Plus]) {
-            let operator = self.tokens[self.current - 1].clone().ty;
-            let right = self.factor();
-            expr = Box::new(BinaryExpr::new(expr, operator, right));
+            let location = Location::of(self.previous());
+            let operator = self.binary_operator()?;
+            let right = self.factor()?;
+            expr = Expr::Binary(BinaryExpr::new(expr, operator, right, location));
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn factor(&mut self) -> Box<dyn Expression> {
-        let mut expr = self.unary();
+    pub fn factor(&mut self) -> ParseResult {
+        let mut expr = self.unary()?;
         while self.match_token(vec![TokenType::Slash, TokenType::Star]) {
-            let operator = self.tokens[self.current - 1].clone().ty;
-            let right = self.unary();
-            expr = Box::new(BinaryExpr::new(expr, operator, right));
+            let location = Location::of(self.previous());
+            let operator = self.binary_operator()?;
+            let right = self.unary()?;
+            expr = Expr::Binary(BinaryExpr::new(expr, operator, right, location));
         }
-        expr
+        Ok(expr)
     }
 
-    pub fn unary(&mut self) -> Box<dyn Expression> {
-        if self.match_token(vec![TokenType::Minus, TokenType::Bang]) {
-            let operator = self.tokens[self.current - 1].clone().ty;
-            let right = self.unary();
-            return Box::new(UnaryExpr {
-                operator: UnaryOperator::from_token(operator).unwrap(),
-                expression: right,
-            });
+    pub fn unary(&mut self) -> ParseResult {
+        self.nested(|parser| {
+            if parser.match_token(vec![TokenType::Minus, TokenType::Bang]) {
+                let token = parser.previous().clone();
+                let operator = UnaryOperator::from_token(token.ty).ok_or_else(|| {
+                    ParseError::new(token.clone(), vec![], "Expected unary operator.")
+                })?;
+                let right = parser.unary()?;
+                return Ok(Expr::Unary(UnaryExpr {
+                    operator,
+                    expression: Box::new(right),
+                    location: Location::of(&token),
+                }));
+            }
+            parser.call()
+        })
+    }
+
+    pub fn call(&mut self) -> ParseResult {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_token(vec![TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(vec![TokenType::LeftBracket]) {
+                expr = self.finish_index(expr)?;
+            } else if self.match_token(vec![TokenType::Dot]) {
+                self.consume(TokenType::Identifier, "Expected property name after '.'.")?;
+                expr = Expr::Get(GetExpr {
+                    object: Box::new(expr),
+                    name: self.name()?,
+                });
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// The arguments of a call to `callee` whose `(` was just consumed.
+    fn finish_call(&mut self, callee: Expr) -> ParseResult {
+        let mut arguments = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() == MAX_ARGUMENTS {
+                    let error = self.error(
+                        vec![],
+                        &format!("Can't have more than {} arguments.", MAX_ARGUMENTS),
+                    );
+                    self.errors.push(error);
+                }
+                arguments.push(self.assignment()?);
+                if !self.match_token(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "Expected ')' after arguments.")?;
+
+        Ok(Expr::Call(CallExpr {
+            callee: Box::new(callee),
+            arguments,
+            location: Location::of(paren),
+        }))
+    }
+
+    /// An index or slice of `object` whose `[` was just consumed.
+    fn finish_index(&mut self, object: Expr) -> ParseResult {
+        let location = Location::of(self.previous());
+        let object = Box::new(object);
+        // An index unless there is a `:`, which is consumed here either way.
+        let start = if self.match_token(vec![TokenType::Colon]) {
+            None
+        } else {
+            let index = Box::new(self.expression()?);
+            if !self.match_token(vec![TokenType::Colon]) {
+                self.consume(TokenType::RightBracket, "Expected ']' after index.")?;
+                return Ok(Expr::Index(IndexExpr {
+                    object,
+                    index,
+                    location,
+                }));
+            }
+            Some(index)
+        };
+        let end = if self.check(&TokenType::RightBracket) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        self.consume(TokenType::RightBracket, "Expected ']' after slice.")?;
+        Ok(Expr::Slice(SliceExpr {
+            object,
+            start,
+            end,
+            location,
+        }))
+    }
+
+    pub fn primary(&mut self) -> ParseResult {
+        if self.match_token(vec![
+            TokenType::False,
+            TokenType::True,
+            TokenType::Nil,
+            TokenType::Number,
+            TokenType::String,
+        ]) {
+            return self.literal();
+        }
+
+        if self.match_token(vec![TokenType::Identifier]) {
+            return Ok(Expr::Variable(VariableExpr { name: self.name()? }));
+        }
+
+        if self.match_token(vec![TokenType::InterpolationStart]) {
+            return self.interpolation();
+        }
+
+        if self.match_token(vec![TokenType::This]) {
+            let keyword = self.previous().clone();
+            if self.class_kind == ClassKind::None {
+                self.report(keyword.clone(), "Can't use 'this' outside of a class.");
+            }
+            return Ok(Expr::This(ThisExpr {
+                keyword: keyword_name(&keyword),
+            }));
+        }
+
+        if self.match_token(vec![TokenType::Super]) {
+            return self.super_expression();
+        }
+
+        if self.match_token(vec![TokenType::LeftBracket]) {
+            return self.list();
+        }
+
+        // A statement starting with `{` is a block, so this is only reached in expressions.
+        if self.match_token(vec![TokenType::LeftBrace]) {
+            return self.map();
+        }
+
+        if self.match_token(vec![TokenType::Fun]) {
+            let start = self.previous().clone();
+            self.consume(TokenType::LeftParen, "Expected '(' after 'fun'.")?;
+            let params = self.parameter_list()?;
+            let body = self.function_body("function")?;
+            return Ok(lambda(&start, params, body));
+        }
+
+        if self.is_arrow_function() {
+            return self.arrow_function();
+        }
+
+        if self.match_token(vec![TokenType::LeftParen]) {
+            let expression = self.expression()?;
+            self.consume(TokenType::RightParen, "Expected ')' after expression.")?;
+            return Ok(Expr::Grouping(GroupingExpr {
+                expression: Box::new(expression),
+            }));
+        }
+
+        // Error productions: a binary operator with nothing on its left.
+        if self.match_token(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
+            return self.missing_left_operand(Parser::comparison);
+        }
+        if self.match_token(vec![
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Less,
+            TokenType::LessEqual,
+        ]) {
+            return self.missing_left_operand(Parser::term);
+        }
+        if self.match_token(vec![TokenType::Plus]) {
+            return self.missing_left_operand(Parser::factor);
+        }
+        if self.match_token(vec![TokenType::Slash, TokenType::Star]) {
+            return self.missing_left_operand(Parser::unary);
+        }
+
+        Err(self.error(EXPRESSION_START.to_vec(), "Expected expression."))
+    }
+
+    /// A list literal whose `[` was just consumed. A trailing comma is allowed.
+    fn list(&mut self) -> ParseResult {
+        let mut elements = Vec::new();
+        while !self.check(&TokenType::RightBracket) {
+            elements.push(self.assignment()?);
+            if !self.match_token(vec![TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expected ']' after list elements.")?;
+        Ok(Expr::List(ListExpr { elements }))
+    }
+
+    /// A map literal whose `{` was just consumed. A trailing comma is allowed.
+    fn map(&mut self) -> ParseResult {
+        let location = Location::of(self.previous());
+        let mut entries = Vec::new();
+        while !self.check(&TokenType::RightBrace) {
+            let key = self.assignment()?;
+            self.consume(TokenType::Colon, "Expected ':' after map key.")?;
+            entries.push((key, self.assignment()?));
+            if !self.match_token(vec![TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after map entries.")?;
+        Ok(Expr::Map(MapExpr { entries, location }))
+    }
+
+    /// Whether the next tokens are the parameters of an arrow function: a parenthesized list
+    /// of names followed by `=>`.
+    fn is_arrow_function(&self) -> bool {
+        if self.peek().ty != TokenType::LeftParen {
+            return false;
+        }
+        let rest = &self.tokens[self.current + 1..];
+        let Some(end) = rest
+            .iter()
+            .position(|token| !matches!(token.ty, TokenType::Identifier | TokenType::Comma))
+        else {
+            return false;
+        };
+        rest[end].ty == TokenType::RightParen
+            && rest
+                .get(end + 1)
+                .is_some_and(|token| token.ty == TokenType::Lambda)
+    }
+
+    /// `(params) => body`, where the body is an expression or a block; `=> {` always starts a
+    /// block.
+    fn arrow_function(&mut self) -> ParseResult {
+        let start = self.advance().clone();
+        let params = self.parameter_list()?;
+        let arrow = self
+            .consume(TokenType::Lambda, "Expected '=>' after parameters.")?
+            .clone();
+        let body = if self.check(&TokenType::LeftBrace) {
+            self.function_body("function")?
+        } else {
+            vec![Stmt::Return(ReturnStmt {
+                value: Some(self.assignment()?),
+                location: Location::of(&arrow),
+            })]
+        };
+        Ok(lambda(&start, params, body))
+    }
+
+    /// `super.method`, whose `super` was just consumed.
+    fn super_expression(&mut self) -> ParseResult {
+        let keyword = self.previous().clone();
+        match self.class_kind {
+            ClassKind::None => {
+                self.report(keyword.clone(), "Can't use 'super' outside of a class.")
+            }
+            ClassKind::Class => self.report(
+                keyword.clone(),
+                "Can't use 'super' in a class with no superclass.",
+            ),
+            ClassKind::Subclass => {}
+        }
+        self.consume(TokenType::Dot, "Expected '.' after 'super'.")?;
+        self.consume(TokenType::Identifier, "Expected superclass method name.")?;
+        Ok(Expr::Super(SuperExpr {
+            keyword: keyword_name(&keyword),
+            method: self.name()?,
+        }))
+    }
+
+    /// Reports the binary operator just consumed as missing its left operand, then parses the
+    /// right operand with `operand` and stands it in for the whole expression.
+    fn missing_left_operand(&mut self, operand: fn(&mut Parser) -> ParseResult) -> ParseResult {
+        let operator = self.previous().clone();
+        let message = format!("Missing left-hand operand for '{}'.", operator.lexeme);
+        self.errors.push(ParseError::new(
+            operator,
+            EXPRESSION_START.to_vec(),
+            message,
+        ));
+        operand(self)
+    }
+
+    /// The literal token just consumed, which is also how the string pieces of an
+    /// interpolation are read.
+    fn literal(&self) -> ParseResult {
+        let token = self.previous();
+        match LiteralValue::from_token(token.ty, token.literal.as_ref()) {
+            Some(value) => Ok(Expr::Literal(LiteralExpr::new(value))),
+            None => Err(ParseError::new(token.clone(), vec![], "Expected literal.")),
+        }
+    }
+
+    /// The binary operator just consumed.
+    fn binary_operator(&self) -> Result<Operator, ParseError> {
+        let token = self.previous();
+        Operator::from_token(token.ty)
+            .ok_or_else(|| ParseError::new(token.clone(), vec![], "Expected binary operator."))
+    }
+
+    pub fn interpolation(&mut self) -> ParseResult {
+        let mut parts = vec![self.literal()?];
+        loop {
+            parts.push(self.expression()?);
+            if self.match_token(vec![TokenType::InterpolationMiddle]) {
+                parts.push(self.literal()?);
+            } else if self.match_token(vec![TokenType::InterpolationEnd]) {
+                parts.push(self.literal()?);
+                return Ok(Expr::Interpolation(InterpolationExpr { parts }));
+            } else {
+                return Err(self.error(
+                    vec![TokenType::InterpolationMiddle, TokenType::InterpolationEnd],
+                    "Expected '}' after interpolated expression.",
+                ));
+            }
+        }
+    }
+
+    /// The identifier just consumed.
+    fn name(&self) -> Result<Name, ParseError> {
+        let token = self.previous();
+        Name::from_token(token)
+            .ok_or_else(|| ParseError::new(token.clone(), vec![], "Expected identifier."))
+    }
+
+    /// A top-level declaration or statement, or `None` if it had a syntax error. When it was
+    /// nested too deeply, the rest of it is skipped here, out of all the levels it went into.
+    fn top_level_declaration(&mut self) -> Option<Stmt> {
+        let start = self.current;
+        self.declaration().unwrap_or_else(|error| {
+            self.errors.push(error);
+            self.too_deep = false;
+            self.skip_statement(start);
+            None
+        })
+    }
+
+    /// A declaration or statement. A syntax error inside it is recorded and skipped over, so
+    /// this returns `None` and the caller carries on with the next one, unless it was nesting
+    /// too deep, which is passed on up to `top_level_declaration`.
+    fn declaration(&mut self) -> Result<Option<Stmt>, ParseError> {
+        let result = if self.match_token(vec![TokenType::Var]) {
+            self.var_declaration()
+        } else if self.match_token(vec![TokenType::Class]) {
+            self.class_declaration()
+        } else if self.check(&TokenType::Fun) && !self.check_next(TokenType::LeftParen) {
+            // `fun (` starts an anonymous function, in an expression statement.
+            self.advance();
+            self.function("function")
+                .map(|function| Stmt::Function(Rc::new(function)))
+        } else {
+            self.statement()
+        };
+
+        match result {
+            Ok(stmt) => Ok(Some(stmt)),
+            Err(error) if self.too_deep => Err(error),
+            Err(error) => {
+                // Unclosed blocks all fail at `Eof`; one error there is enough.
+                if self
+                    .errors
+                    .last()
+                    .is_none_or(|last| last.token.span != error.token.span)
+                {
+                    self.errors.push(error);
+                }
+                self.synchronize();
+                Ok(None)
+            }
+        }
+    }
+
+    /// A class whose `class` was just consumed: its name, superclass and methods.
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::Identifier, "Expected class name.")?;
+        let name = self.name()?;
+
+        let superclass = if self.match_token(vec![TokenType::Less]) {
+            self.consume(TokenType::Identifier, "Expected superclass name.")?;
+            let superclass = self.name()?;
+            if superclass.symbol == name.symbol {
+                let token = self.previous().clone();
+                self.report(token, "A class can't inherit from itself.");
+            }
+            Some(VariableExpr { name: superclass })
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body.")?;
+        let kind = if superclass.is_some() {
+            ClassKind::Subclass
+        } else {
+            ClassKind::Class
+        };
+        let enclosing = std::mem::replace(&mut self.class_kind, kind);
+        let methods = self.methods();
+        self.class_kind = enclosing;
+
+        Ok(Stmt::Class(ClassStmt {
+            name,
+            superclass,
+            methods: methods?,
+        }))
+    }
+
+    /// The methods of a class body, up to and including its `}`.
+    fn methods(&mut self) -> Result<Vec<Rc<FunctionStmt>>, ParseError> {
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(Rc::new(self.function("method")?));
+        }
+        self.consume(TokenType::RightBrace, "Expected '}' after class body.")?;
+        Ok(methods)
+    }
+
+    /// The name, parameters and body of a function whose `fun` was just consumed, or of a
+    /// method. `kind` says what is being declared, for error messages.
+    fn function(&mut self, kind: &str) -> Result<FunctionStmt, ParseError> {
+        self.consume(TokenType::Identifier, &format!("Expected {} name.", kind))?;
+        let name = self.name()?;
+        let kind = if kind == "method" && name.symbol == Symbol::intern("init") {
+            "initializer"
+        } else {
+            kind
+        };
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expected '(' after {} name.", kind),
+        )?;
+        let params = self.parameter_list()?;
+        let body = self.function_body(kind)?;
+        Ok(FunctionStmt { name, params, body })
+    }
+
+    /// The parameters of a function whose `(` was just consumed, up to and including the `)`.
+    fn parameter_list(&mut self) -> Result<Vec<Name>, ParseError> {
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() == MAX_ARGUMENTS {
+                    let error = self.error(
+                        vec![],
+                        &format!("Can't have more than {} parameters.", MAX_ARGUMENTS),
+                    );
+                    self.errors.push(error);
+                }
+                self.consume(TokenType::Identifier, "Expected parameter name.")?;
+                params.push(self.name()?);
+                if !self.match_token(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after parameters.")?;
+        Ok(params)
+    }
+
+    /// A `{ ... }` function body.
+    fn function_body(&mut self, kind: &str) -> Result<Vec<Stmt>, ParseError> {
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expected '{{' before {} body.", kind),
+        )?;
+        // Loops outside the function can't be broken out of from inside it.
+        let loops = std::mem::take(&mut self.loops);
+        let enclosing = std::mem::replace(&mut self.in_initializer, kind == "initializer");
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.in_initializer = enclosing;
+        self.loops = loops;
+        body
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::Identifier, "Expected variable name.")?;
+        let name = self.name()?;
+
+        let initializer = if self.match_token(vec![TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expected ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var(VarStmt { name, initializer }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ParseError> {
+        self.nested(|parser| {
+            if parser.match_token(vec![TokenType::Print]) {
+                let location = Location::of(parser.previous());
+                let expression = parser.expression()?;
+                parser.consume(TokenType::Semicolon, "Expected ';' after value.")?;
+                return Ok(Stmt::Print(PrintStmt {
+                    expression,
+                    location,
+                }));
+            }
+            if parser.match_token(vec![TokenType::LeftBrace]) {
+                return Ok(Stmt::Block(BlockStmt {
+                    statements: parser.block()?,
+                }));
+            }
+            if parser.match_token(vec![TokenType::If]) {
+                return parser.if_statement();
+            }
+            if parser.match_token(vec![TokenType::While]) {
+                return parser.while_statement(None);
+            }
+            if parser.match_token(vec![TokenType::For]) {
+                return parser.for_statement(None);
+            }
+            if parser.check(&TokenType::Identifier) && parser.check_next(TokenType::Colon) {
+                return parser.labeled_statement();
+            }
+            if parser.match_token(vec![TokenType::Return]) {
+                return parser.return_statement();
+            }
+            if parser.match_token(vec![TokenType::Break]) {
+                let (label, location) = parser.jump("break")?;
+                return Ok(Stmt::Break(BreakStmt { label, location }));
+            }
+            if parser.match_token(vec![TokenType::Continue]) {
+                let (label, location) = parser.jump("continue")?;
+                return Ok(Stmt::Continue(ContinueStmt { label, location }));
+            }
+
+            let expression = parser.expression()?;
+            parser.consume(TokenType::Semicolon, "Expected ';' after expression.")?;
+            Ok(Stmt::Expression(ExpressionStmt { expression }))
+        })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.function_depth == 0 {
+            self.report(keyword.clone(), "Can't return from top-level code.");
+        }
+
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            if self.in_initializer {
+                self.report(keyword.clone(), "Can't return a value from an initializer.");
+            }
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after return value.")?;
+        Ok(Stmt::Return(ReturnStmt {
+            value,
+            location: Location::of(&keyword),
+        }))
+    }
+
+    /// The optional label and `;` of a `break` or `continue`, which must be inside a loop with
+    /// that label.
+    fn jump(&mut self, keyword: &str) -> Result<(Option<Name>, Location), ParseError> {
+        let keyword_token = self.previous().clone();
+        let location = Location::of(&keyword_token);
+        let label = if self.match_token(vec![TokenType::Identifier]) {
+            Some(self.name()?)
+        } else {
+            None
+        };
+
+        if self.loops.is_empty() {
+            self.report(
+                keyword_token,
+                &format!("Can't use '{}' outside of a loop.", keyword),
+            );
+        } else if let Some(label) = label {
+            if !self.loops.contains(&Some(label.symbol)) {
+                let token = self.previous().clone();
+                self.report(
+                    token,
+                    &format!("No enclosing loop is labeled '{}'.", label.symbol),
+                );
+            }
         }
-        self.primary()
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expected ';' after '{}'.", keyword),
+        )?;
+        Ok((label, location))
     }
 
-    pub fn primary(&mut self) -> Box<dyn Expression> {
-        if self.match_token(vec![TokenType::False]) {
-            return Box::new(LiteralExpr::new(Either::Right(TokenType::False)));
+    /// `label: while ...` or `label: for ...`.
+    fn labeled_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.advance();
+        let label = self.name()?;
+        if self.loops.contains(&Some(label.symbol)) {
+            let token = self.previous().clone();
+            self.report(
+                token,
+                &format!(
+                    "Label '{}' is already used by an enclosing loop.",
+                    label.symbol
+                ),
+            );
         }
+        self.advance();
 
-        if self.match_token(vec![TokenType::True]) {
-            return Box::new(LiteralExpr::new(Either::Right(TokenType::True)));
+        if self.match_token(vec![TokenType::While]) {
+            self.while_statement(Some(label))
+        } else if self.match_token(vec![TokenType::For]) {
+            self.for_statement(Some(label))
+        } else {
+            Err(self.error(
+                vec![TokenType::While, TokenType::For],
+                "Expected a loop after label.",
+            ))
         }
+    }
+
+    /// The body of a loop called `label`, in which `break` and `continue` can be used.
+    fn loop_body(&mut self, label: Option<Name>) -> Result<Stmt, ParseError> {
+        self.loops.push(label.map(|label| label.symbol));
+        let body = self.statement();
+        self.loops.pop();
+        body
+    }
+
+    /// A parenthesized condition, as after `if` and `while`.
+    fn condition(&mut self, keyword: &str) -> ParseResult {
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expected '(' after '{}'.", keyword),
+        )?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after condition.")?;
+        Ok(condition)
+    }
+
+    /// An `else` always belongs to the nearest `if`.
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.condition("if")?;
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(vec![TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Stmt::If(IfStmt {
+            condition,
+            then_branch,
+            else_branch,
+        }))
+    }
+
+    fn while_statement(&mut self, label: Option<Name>) -> Result<Stmt, ParseError> {
+        let condition = self.condition("while")?;
+        let body = Box::new(self.loop_body(label)?);
+        Ok(Stmt::While(WhileStmt {
+            condition,
+            body,
+            increment: None,
+            label,
+        }))
+    }
+
+    /// `for (initializer; condition; increment) body` becomes
+    /// `{ initializer; while (condition) body }`, with the increment run by the loop after
+    /// each pass through the body.
+    fn for_statement(&mut self, label: Option<Name>) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'for'.")?;
+        let initializer = if self.match_token(vec![TokenType::Semicolon]) {
+            None
+        } else if self.match_token(vec![TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            let expression = self.expression()?;
+            self.consume(TokenType::Semicolon, "Expected ';' after loop initializer.")?;
+            Some(Stmt::Expression(ExpressionStmt { expression }))
+        };
 
-        if self.match_token(vec![TokenType::Nil]) {
-            return Box::new(LiteralExpr::new(Either::Right(TokenType::Nil)));
+        let condition = if self.check(&TokenType::Semicolon) {
+            Expr::Literal(LiteralExpr::new(LiteralValue::Bool(true)))
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after loop condition.")?;
+
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses.")?;
+
+        let body = self.loop_body(label)?;
+        let mut lowered = Stmt::While(WhileStmt {
+            condition,
+            body: Box::new(body),
+            increment,
+            label,
+        });
+        if let Some(initializer) = initializer {
+            lowered = Stmt::Block(BlockStmt {
+                statements: vec![initializer, lowered],
+            });
         }
+        Ok(lowered)
+    }
 
-        if self.match_token(vec![TokenType::Number, TokenType::String]) {
-            return Box::new(LiteralExpr::new(Either::Left(
-                self.tokens[self.current - 1].literal.clone().unwrap(),
-            )));
+    /// The statements of a block whose `{` was just consumed, up to and including its `}`.
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.nested(|parser| {
+            let mut statements = Vec::new();
+            while !parser.check(&TokenType::RightBrace) && !parser.is_at_end() {
+                statements.extend(parser.declaration()?);
+            }
+            parser.consume(TokenType::RightBrace, "Expected '}' after block.")?;
+            Ok(statements)
+        })
+    }
+
+    /// Parses a whole script. The `Err` side holds every syntax error found.
+    pub fn parse(&mut self) -> Result<Program, Vec<ParseError>> {
+        debug!(target: "parser", tokens = self.tokens.len(), "parsing");
+        let mut program = Program::default();
+        while !self.is_at_end() {
+            let start = self.current;
+            if let Some(statement) = self.top_level_declaration() {
+                program.push(statement, start..self.current);
+            }
         }
+        self.finish(program)
+    }
 
-        panic!("Expected expression.")
+    /// Parses one top-level declaration and gives back the range of tokens it took, or `None`
+    /// at the end of the tokens. A reparse goes through the edited part of a script with this.
+    pub fn next_declaration(&mut self) -> Option<Declaration> {
+        if self.is_at_end() {
+            return None;
+        }
+        let start = self.current;
+        let statement = self.top_level_declaration();
+        Some(self.finish(statement).map(|statement| {
+            let statement = statement.expect("a declaration with no errors has a statement");
+            (statement, start..self.current)
+        }))
     }
 
-    pub fn parse(&mut self) -> Box<dyn Expression> {
-        self.expression()
+    /// Parses a single expression that makes up all of the input, as typed at the REPL.
+    pub fn parse_expression(&mut self) -> Result<Expr, Vec<ParseError>> {
+        let result = self.expression().and_then(|expression| {
+            if self.is_at_end() {
+                Ok(expression)
+            } else {
+                Err(self.error(vec![TokenType::Eof], "Expected end of expression."))
+            }
+        });
+        match result {
+            Ok(expression) => self.finish(expression),
+            Err(error) => {
+                self.errors.push(error);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    fn finish<T>(&mut self, parsed: T) -> Result<T, Vec<ParseError>> {
+        if self.errors.is_empty() {
+            Ok(parsed)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+}
+
+/// An anonymous function starting at `start`.
+fn lambda(start: &Token, params: Vec<Name>, body: Vec<Stmt>) -> Expr {
+    let name = Name {
+        symbol: Symbol::intern("lambda"),
+        location: Location::of(start),
+    };
+    Expr::Lambda(LambdaExpr {
+        declaration: Rc::new(FunctionStmt { name, params, body }),
+    })
+}
+
+/// The name `this` or `super` stands for, at the keyword.
+fn keyword_name(keyword: &Token) -> Name {
+    Name {
+        symbol: Symbol::intern(&keyword.lexeme),
+        location: Location::of(keyword),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::ops::Deref;
-
     use super::*;
-    use crate::parser::astprinter::{self, Astprinter};
-    use crate::parser::expressions::Visitor;
+    use crate::parser::astprinter::Astprinter;
+    use crate::scanner::scanner::Scanner;
+    use crate::scanner::source::{FileId, Span};
     use crate::scanner::token::Token;
-    use crate::scanner::tokentype::TokenType;
+    use crate::scanner::tokentype::{Literal, TokenType};
 
     #[test]
     fn test_parser_equality_expression() {
         // Tokens representing the expression "4 == 4"
         let tokens = vec![
             Token {
+                file: FileId::default(),
+                span: Span::new(0, 1),
                 col: 1,
                 line: 1,
                 ty: TokenType::Number,
-                lexeme: String::from("4"),
+                lexeme: "4".into(),
                 literal: Some(Literal::Number(4.0)),
             },
             Token {
+                file: FileId::default(),
+                span: Span::new(1, 2),
                 col: 2,
                 line: 1,
                 ty: TokenType::EqualEqual,
-                lexeme: String::from("=="),
+                lexeme: "==".into(),
                 literal: None,
             },
             Token {
+                file: FileId::default(),
+                span: Span::new(2, 3),
                 col: 3,
                 line: 1,
                 ty: TokenType::Number,
-                lexeme: String::from("4"),
+                lexeme: "4".into(),
                 literal: Some(Literal::Number(4.0)),
             },
             Token {
+                file: FileId::default(),
+                span: Span::new(3, 4),
                 col: 4,
                 line: 1,
                 ty: TokenType::Eof,
-                lexeme: String::from(""),
+                lexeme: "".into(),
                 literal: None,
             },
         ];
 
         let mut parser = Parser::new(tokens);
-        let expression = parser.parse();
+        let expression = parser.parse_expression().unwrap();
+
+        println!("{:?}", expression);
+        assert_eq!(expression.accept(&mut Astprinter), "(4 EqualEqual 4)");
+    }
+
+    #[test]
+    fn test_parser_string_interpolation() {
+        let mut scanner = Scanner::new(String::from(r#""You have ${3 + 1} items""#));
+        let mut parser = Parser::new(scanner.scan_tokens().unwrap());
+        let expression = parser.parse_expression().unwrap();
+
+        assert_eq!(
+            expression.accept(&mut Astprinter),
+            "(interpolate You have  (3 Plus 1)  items)"
+        );
+    }
+
+    fn parse_errors(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(String::from(source)).scan_tokens().unwrap();
+        Parser::new(tokens)
+            .parse()
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_parser_reports_every_syntax_error() {
+        assert_eq!(
+            parse_errors("(1 + 2;\n3 + ;\n4 5;\n6 + 7;"),
+            vec![
+                "[line 1:7] Error at ';': Expected ')' after expression.",
+                "[line 2:5] Error at ';': Expected expression.",
+                "[line 3:3] Error at '5': Expected ';' after expression.",
+            ]
+        );
+        assert_eq!(
+            parse_errors("print \"hi\" 1;\nvar = 2;\n1 +"),
+            vec![
+                "[line 1:12] Error at '1': Expected ';' after value.",
+                "[line 2:5] Error at '=': Expected variable name.",
+                "[line 3:4] Error at end: Expected expression.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_error_has_token_and_expected_set() {
+        let tokens = Scanner::new(String::from("(1 + 2")).scan_tokens().unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].token.ty, TokenType::Eof);
+        assert_eq!(errors[0].expected, vec![TokenType::RightParen]);
+    }
+
+    #[test]
+    fn test_binary_operator_without_left_operand() {
+        assert_eq!(
+            parse_errors("* 3;\n1 + == 2 * 3;"),
+            vec![
+                "[line 1:1] Error at '*': Missing left-hand operand for '*'.",
+                "[line 2:5] Error at '==': Missing left-hand operand for '=='.",
+            ]
+        );
+        assert_eq!(
+            parse_errors("== 1 + 2 > 3; <= -1 and"),
+            vec![
+                "[line 1:1] Error at '==': Missing left-hand operand for '=='.",
+                "[line 1:15] Error at '<=': Missing left-hand operand for '<='.",
+                "[line 1:24] Error at end: Expected expression.",
+            ]
+        );
+    }
+
+    fn parse_program(source: &str) -> String {
+        let tokens = Scanner::new(String::from(source)).scan_tokens().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        Astprinter.print_program(&program)
+    }
+
+    #[test]
+    fn test_statements() {
+        assert_eq!(
+            parse_program("var a = 1;\nvar b;\n{ var a = a + 2; print a; }\nb == a;"),
+            "(var a 1)\n(var b)\n(block (var a (a Plus 2)) (print a))\n(expr (b EqualEqual a))"
+        );
+    }
+
+    #[test]
+    fn test_print_data_file_parses() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test/print.lox");
+        let source = std::fs::read_to_string(path).unwrap();
+        assert_eq!(parse_program(&source), "(print Hello World)");
+    }
+
+    #[test]
+    fn test_errors_inside_blocks_are_recovered() {
+        assert_eq!(
+            parse_errors("{\n  var = 1;\n  print 2\n}\nvar ok = 3;\n{"),
+            vec![
+                "[line 2:7] Error at '=': Expected variable name.",
+                "[line 4:1] Error at '}': Expected ';' after value.",
+                "[line 6:2] Error at end: Expected '}' after block.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        assert_eq!(
+            parse_program("if (a) print 1; else { print 2; }\nif (b) if (c) d; else e;"),
+            "(if a (print 1) (block (print 2)))\n(if b (if c (expr d) (expr e)))"
+        );
+    }
+
+    #[test]
+    fn test_while_and_for_loops() {
+        assert_eq!(
+            parse_program("while (a < 3) print a;"),
+            "(while (a Less 3) (print a))"
+        );
+        assert_eq!(
+            parse_program("for (var i = 0; i < 3; i + 1) print i;"),
+            "(block (var i 0) (while (i Less 3) (print i) (i Plus 1)))"
+        );
+        assert_eq!(parse_program("for (;;) x;"), "(while true (expr x))");
+        assert_eq!(
+            parse_program("for (a; b;) {}"),
+            "(block (expr a) (while b (block )))"
+        );
+    }
+
+    #[test]
+    fn test_logical_operator_precedence() {
+        assert_eq!(
+            parse_program("a or b and c == d;\n(a or b) and c;"),
+            "(expr (a or (b and (c EqualEqual d))))\n(expr ((group (a or b)) and c))"
+        );
+    }
+
+    #[test]
+    fn test_control_flow_errors() {
+        assert_eq!(
+            parse_errors("if a) print 1;\nwhile (b print 2;\nfor (var i = 0; i < 1) print i;"),
+            vec![
+                "[line 1:4] Error at 'a': Expected '(' after 'if'.",
+                "[line 2:10] Error at 'print': Expected ')' after condition.",
+                "[line 3:22] Error at ')': Expected ';' after loop condition.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assignment() {
+        assert_eq!(
+            parse_program("a = b = 1 + c;\nvar x = y = nil;"),
+            "(expr (= a (= b (1 Plus c))))\n(var x (= y nil))"
+        );
+        assert_eq!(
+            parse_errors("a + b = 1;\n(a) = 2;\nc = 3 = d;"),
+            vec![
+                "[line 1:7] Error at '=': Invalid assignment target.",
+                "[line 2:5] Error at '=': Invalid assignment target.",
+                "[line 3:7] Error at '=': Invalid assignment target.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions_and_calls() {
+        assert_eq!(
+            parse_program("fun add(a, b) { return a + b; }\nadd(1, 2)(3);\nfun f() { return; }"),
+            "(fun add (a b) (return (a Plus b)))\n(expr (call (call add 1 2) 3))\n(fun f () (return))"
+        );
+        assert_eq!(
+            parse_errors("return 1;\nf(1;\nfun 1() {}\nfun g(a b) {}"),
+            vec![
+                "[line 1:1] Error at 'return': Can't return from top-level code.",
+                "[line 2:4] Error at ';': Expected ')' after arguments.",
+                "[line 3:5] Error at '1': Expected function name.",
+                "[line 4:9] Error at 'b': Expected ')' after parameters.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_too_many_arguments() {
+        let errors = parse_errors(&format!("f({});", vec!["1"; 256].join(", ")));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].ends_with("Error at '1': Can't have more than 255 arguments."));
+        parse_program(&format!("f({});", vec!["1"; 255].join(", ")));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(
+            parse_program(
+                "class B < A { init(x) { this.x = x; } get() { return super.get() + this.x; } }"
+            ),
+            "(class B < A (fun init (x) (expr (.= this x x))) \
+             (fun get () (return ((call (super get)) Plus (. this x)))))"
+        );
+        assert_eq!(
+            parse_program("a.b.c = d.e(f).g;"),
+            "(expr (.= (. a b) c (. (call (. d e) f) g)))"
+        );
+        assert_eq!(
+            parse_errors(
+                "print this;\nfun f() { super.g(); }\nclass A { m() { super.m(); } }\n\
+                 class B < B {}\nclass C { init() { return 1; } }\na.;"
+            ),
+            vec![
+                "[line 1:7] Error at 'this': Can't use 'this' outside of a class.",
+                "[line 2:11] Error at 'super': Can't use 'super' outside of a class.",
+                "[line 3:17] Error at 'super': Can't use 'super' in a class with no superclass.",
+                "[line 4:11] Error at 'B': A class can't inherit from itself.",
+                "[line 5:20] Error at 'return': Can't return a value from an initializer.",
+                "[line 6:3] Error at ';': Expected property name after '.'.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_anonymous_functions() {
+        assert_eq!(
+            parse_program(
+                "var f = fun (a, b) { return a + b; };\nmap((x) => x * 2, ()=>{ print 1; });"
+            ),
+            "(var f (lambda (a b) (return (a Plus b))))\n\
+             (expr (call map (lambda (x) (return (x Star 2))) (lambda () (print 1))))"
+        );
+        // `fun (` at the start of a statement is an expression, here called straight away.
+        assert_eq!(
+            parse_program("fun () { print 1; }();\n(a);"),
+            "(expr (call (lambda () (print 1))))\n(expr (group a))"
+        );
+        assert_eq!(
+            parse_errors("var f = fun x() {};\nvar g = (a, 1) => a;\nclass A { init() { fun () { return 1; }; } }"),
+            vec![
+                "[line 1:13] Error at 'x': Expected '(' after 'fun'.",
+                "[line 2:16] Error at '=>': Expected ';' after variable declaration.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lists_indexing_and_slices() {
+        assert_eq!(
+            parse_program("var xs = [1, [2], ];\nxs[0][-1] = xs[1:];\nprint [][:2];\nxs[:];"),
+            "(var xs (list 1 (list 2)))\n\
+             (expr (index= (index xs 0) (Minus 1) (slice xs 1 _)))\n\
+             (print (slice (list) _ 2))\n\
+             (expr (slice xs _ _))"
+        );
+        assert_eq!(
+            parse_errors("xs[];\nxs[1 2];\n[1 2];\nxs[1:2] = 3;"),
+            vec![
+                "[line 1:4] Error at ']': Expected expression.",
+                "[line 2:6] Error at '2': Expected ']' after index.",
+                "[line 3:4] Error at '2': Expected ']' after list elements.",
+                "[line 4:9] Error at '=': Invalid assignment target.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_literals() {
+        assert_eq!(
+            parse_program("var m = {\"a\": 1, b: {}};\n{ print {1: 2}[1]; }\nm[\"c\"] = 3;"),
+            "(var m (map (a 1) (b (map))))\n\
+             (block (print (index (map (1 2)) 1)))\n\
+             (expr (index= m c 3))"
+        );
+        assert_eq!(
+            parse_errors("var m = {\"a\" 1};\nvar n = {\"a\": 1 \"b\": 2};"),
+            vec![
+                "[line 1:14] Error at '1': Expected ':' after map key.",
+                "[line 2:17] Error at '\"b\"': Expected '}' after map entries.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ternary_and_comma_operators() {
+        assert_eq!(
+            parse_program("a ? b : c ? d : e;\nx = a or b ? 1 : 2;\nc ? x = 1, 2 : y;"),
+            "(expr (a ? b : (c ? d : e)))\n\
+             (expr (= x ((a or b) ? 1 : 2)))\n\
+             (expr (c ? ((= x 1) Comma 2) : y))"
+        );
+        // Commas that separate arguments and elements aren't operators, unless grouped.
+        assert_eq!(
+            parse_program("a, b = 1, c;\nf(a, (b, c));\n[1, 2];\nprint (x) => x, 1;"),
+            "(expr ((a Comma (= b 1)) Comma c))\n\
+             (expr (call f a (group (b Comma c))))\n\
+             (expr (list 1 2))\n\
+             (print ((lambda (x) (return x)) Comma 1))"
+        );
+        assert_eq!(
+            parse_errors("var a = b ? c;\nvar d = e ? f : g ? h;"),
+            vec![
+                "[line 1:11] Error at '?': Expected ':' to go with this '?'.",
+                "[line 2:19] Error at '?': Expected ':' to go with this '?'.",
+            ]
+        );
+        assert_eq!(
+            parse_errors("a ? b : c = d;"),
+            vec!["[line 1:11] Error at '=': Invalid assignment target."]
+        );
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        assert_eq!(
+            parse_program(
+                "outer: for (;; i = i + 1) { while (a) { if (b) break outer; continue; } break; }"
+            ),
+            "(label outer (while true (block (while a (block (if b (break outer)) (continue))) \
+             (break)) (= i (i Plus 1))))"
+        );
+        assert_eq!(
+            parse_errors(
+                "break;\nwhile (a) { fun f() { continue; } }\nl: while (a) { break m; }\n\
+                 l: while (a) l: while (b) {}\nl: print 1;\nwhile (a) break"
+            ),
+            vec![
+                "[line 1:1] Error at 'break': Can't use 'break' outside of a loop.",
+                "[line 2:23] Error at 'continue': Can't use 'continue' outside of a loop.",
+                "[line 3:22] Error at 'm': No enclosing loop is labeled 'm'.",
+                "[line 4:14] Error at 'l': Label 'l' is already used by an enclosing loop.",
+                "[line 5:4] Error at 'print': Expected a loop after label.",
+                "[line 6:16] Error at end: Expected ';' after 'break'.",
+            ]
+        );
+        // Recovery stops at `break` and `continue`, so errors in them are still found.
+        assert_eq!(
+            parse_errors(
+                "while (a) { print 1 2 break m; }\nl: while (a) { print f(1)) continue k; }"
+            ),
+            vec![
+                "[line 1:21] Error at '2': Expected ';' after value.",
+                "[line 1:29] Error at 'm': No enclosing loop is labeled 'm'.",
+                "[line 2:26] Error at ')': Expected ';' after value.",
+                "[line 2:37] Error at 'k': No enclosing loop is labeled 'k'.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_too_much_nesting() {
+        let n = 100_000;
+        for nested in [
+            format!("print {}1{};", "(".repeat(n), ")".repeat(n)),
+            format!("print {}1;", "-".repeat(2 * n)),
+            format!("{}{}", "{".repeat(n), "}".repeat(n)),
+            format!("if (a) {}{} else {{}}", "{".repeat(n), "}".repeat(n)),
+            format!("print {}{};", "f(".repeat(n), ")".repeat(n)),
+            format!("fun f() {{ return {}{}; }}", "[".repeat(n), "]".repeat(n)),
+        ] {
+            let errors = parse_errors(&format!(
+                "{}
+var = 1;",
+                nested
+            ));
+            assert_eq!(errors.len(), 2, "{:?}", errors);
+            assert!(errors[0].starts_with("[line 1:"));
+            assert!(errors[0].ends_with("Too much nesting."));
+            assert_eq!(
+                errors[1],
+                "[line 2:5] Error at '=': Expected variable name."
+            );
+        }
 
-        println!("{:?}", expression.deref());
-        assert_eq!("(4 == 4)", "(4 == 4)");
+        parse_program(&format!("print {}1{};", "(".repeat(40), ")".repeat(40)));
+        parse_program(&format!("{}{}", "{".repeat(60), "}".repeat(60)));
     }
 }
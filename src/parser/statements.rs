@@ -0,0 +1,149 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::expressions::{Expr, Location, Name, VariableExpr};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Expression(ExpressionStmt),
+    Print(PrintStmt),
+    Var(VarStmt),
+    Block(BlockStmt),
+    If(IfStmt),
+    While(WhileStmt),
+    Function(Rc<FunctionStmt>),
+    Return(ReturnStmt),
+    Class(ClassStmt),
+    Break(BreakStmt),
+    Continue(ContinueStmt),
+}
+
+impl Stmt {
+    pub fn accept<R>(&self, visitor: &mut dyn StmtVisitor<R>) -> R {
+        match self {
+            Stmt::Expression(stmt) => visitor.visit_expressionstmt(stmt),
+            Stmt::Print(stmt) => visitor.visit_printstmt(stmt),
+            Stmt::Var(stmt) => visitor.visit_varstmt(stmt),
+            Stmt::Block(stmt) => visitor.visit_blockstmt(stmt),
+            Stmt::If(stmt) => visitor.visit_ifstmt(stmt),
+            Stmt::While(stmt) => visitor.visit_whilestmt(stmt),
+            Stmt::Function(stmt) => visitor.visit_functionstmt(stmt),
+            Stmt::Return(stmt) => visitor.visit_returnstmt(stmt),
+            Stmt::Class(stmt) => visitor.visit_classstmt(stmt),
+            Stmt::Break(stmt) => visitor.visit_breakstmt(stmt),
+            Stmt::Continue(stmt) => visitor.visit_continuestmt(stmt),
+        }
+    }
+}
+
+pub trait StmtVisitor<R> {
+    fn visit_expressionstmt(&mut self, stmt: &ExpressionStmt) -> R;
+    fn visit_printstmt(&mut self, stmt: &PrintStmt) -> R;
+    fn visit_varstmt(&mut self, stmt: &VarStmt) -> R;
+    fn visit_blockstmt(&mut self, stmt: &BlockStmt) -> R;
+    fn visit_ifstmt(&mut self, stmt: &IfStmt) -> R;
+    fn visit_whilestmt(&mut self, stmt: &WhileStmt) -> R;
+    fn visit_functionstmt(&mut self, stmt: &Rc<FunctionStmt>) -> R;
+    fn visit_returnstmt(&mut self, stmt: &ReturnStmt) -> R;
+    fn visit_classstmt(&mut self, stmt: &ClassStmt) -> R;
+    fn visit_breakstmt(&mut self, stmt: &BreakStmt) -> R;
+    fn visit_continuestmt(&mut self, stmt: &ContinueStmt) -> R;
+}
+
+/// An expression evaluated for its side effects: `f(x);`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpressionStmt {
+    pub expression: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintStmt {
+    pub expression: Expr,
+    /// Where the `print` keyword is.
+    pub location: Location,
+}
+
+/// `var name;` or `var name = initializer;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarStmt {
+    pub name: Name,
+    pub initializer: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStmt {
+    pub statements: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfStmt {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+/// A `while` loop. `for` loops are desugared into one of these inside a block, with their
+/// increment kept apart from the body so that `continue` still runs it. `label` is the name
+/// given with `label: while ...`, for `break label;` and `continue label;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhileStmt {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    pub increment: Option<Expr>,
+    pub label: Option<Name>,
+}
+
+/// `fun name(params) { body }`. Shared, since every function value made from the declaration
+/// points back to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionStmt {
+    pub name: Name,
+    pub params: Vec<Name>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnStmt {
+    pub value: Option<Expr>,
+    /// Where the `return` keyword is.
+    pub location: Location,
+}
+
+/// `class name < superclass { methods }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassStmt {
+    pub name: Name,
+    pub superclass: Option<VariableExpr>,
+    pub methods: Vec<Rc<FunctionStmt>>,
+}
+
+/// `break;` or `break label;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakStmt {
+    pub label: Option<Name>,
+    /// Where the `break` keyword is.
+    pub location: Location,
+}
+
+/// `continue;` or `continue label;`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContinueStmt {
+    pub label: Option<Name>,
+    /// Where the `continue` keyword is.
+    pub location: Location,
+}
+
+/// A whole script: its top-level statements in order, and the range of tokens each one was
+/// parsed from, so that a reparse after an edit can tell which ones the edit touched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl Program {
+    pub fn push(&mut self, statement: Stmt, range: Range<usize>) {
+        self.statements.push(statement);
+        self.ranges.push(range);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use super::source::{FileId, Span};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub file: FileId,
+    pub span: Span,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl ScanError {
+    pub fn new(line: usize, col: usize, message: impl Into<String>) -> Self {
+        ScanError {
+            file: FileId::default(),
+            span: Span::default(),
+            line,
+            col,
+            message: message.into(),
+        }
+    }
+
+    /// The same error, pointing at `span` in `file`.
+    pub fn at(self, file: FileId, span: Span) -> Self {
+        ScanError { file, span, ..self }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error: {}",
+            self.line, self.col, self.message
+        )
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+/// Something suspicious the scanner noticed that doesn't stop the source from scanning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanWarning {
+    pub file: FileId,
+    pub span: Span,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl ScanWarning {
+    pub fn new(line: usize, col: usize, message: impl Into<String>) -> Self {
+        ScanWarning {
+            file: FileId::default(),
+            span: Span::default(),
+            line,
+            col,
+            message: message.into(),
+        }
+    }
+
+    /// The same warning, pointing at `span` in `file`.
+    pub fn at(self, file: FileId, span: Span) -> Self {
+        ScanWarning { file, span, ..self }
+    }
+}
+
+impl fmt::Display for ScanWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Warning: {}",
+            self.line, self.col, self.message
+        )
+    }
+}
@@ -0,0 +1,281 @@
+use std::ops::Range;
+
+use super::{error::ScanError, scanner::Scanner, source::Span, token::Token, tokentype::TokenType};
+
+/// Replaces the bytes in `range` of the old text with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, replacement: impl Into<String>) -> Self {
+        TextEdit {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// How much the edit moves the text that follows it.
+    pub fn delta(&self) -> isize {
+        self.replacement.len() as isize - self.range.len() as isize
+    }
+}
+
+/// How the tokens kept after an edit moved: by `bytes` in the source and by `lines`, and by
+/// `cols` if they are on `line`, the old line where the kept tokens start.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Shift {
+    pub bytes: isize,
+    pub lines: isize,
+    pub line: usize,
+    pub cols: i64,
+}
+
+impl Shift {
+    pub fn is_empty(&self) -> bool {
+        self.bytes == 0 && self.lines == 0 && self.cols == 0
+    }
+
+    pub fn span(&self, span: Span) -> Span {
+        Span::new(
+            (span.start as isize + self.bytes) as usize,
+            (span.end as isize + self.bytes) as usize,
+        )
+    }
+
+    /// Where something that was at `line` and `col` is now.
+    pub fn position(&self, line: usize, col: u32) -> (usize, u32) {
+        let col = if line == self.line {
+            (col as i64 + self.cols) as u32
+        } else {
+            col
+        };
+        ((line as isize + self.lines) as usize, col)
+    }
+}
+
+/// Applies `edit` to `source` and brings `tokens`, the successful scan of the old `source`, up
+/// to date by scanning again only around the edit. The tokens are exactly what a full scan of
+/// the new source would give.
+///
+/// Scanning restarts a token before the edit and stops as soon as it produces a token that
+/// lines up with an old one after the edit; from there on the old tokens are kept, shifted by
+/// the size of the edit. On success returns the range of `tokens` that was scanned again. On
+/// error `source` has been edited but `tokens` is left as it was.
+pub fn relex(
+    source: &mut String,
+    tokens: &mut Vec<Token>,
+    edit: &TextEdit,
+) -> Result<Range<usize>, Vec<ScanError>> {
+    relex_shifted(source, tokens, edit).map(|(rescanned, _)| rescanned)
+}
+
+/// `relex`, also giving back how the tokens after the range scanned again were moved.
+pub fn relex_shifted(
+    source: &mut String,
+    tokens: &mut Vec<Token>,
+    edit: &TextEdit,
+) -> Result<(Range<usize>, Shift), Vec<ScanError>> {
+    let depths = interpolation_depths(tokens);
+
+    // The last token ending before the edit could still be extended by it, so back up one more
+    // and then to a point outside any string interpolation.
+    let before_edit = tokens.partition_point(|token| token.span.end < edit.range.start);
+    let mut restart = before_edit.saturating_sub(1);
+    while restart > 0 && depths[restart] != 0 {
+        restart -= 1;
+    }
+    let (position, line, col) = if restart == 0 {
+        (0, 1, 1)
+    } else {
+        let token = &tokens[restart];
+        (token.span.start, token.line, token.col as usize)
+    };
+
+    source.replace_range(edit.range.clone(), &edit.replacement);
+    let delta = edit.delta();
+    let edit_end = edit.range.start + edit.replacement.len();
+
+    let file = tokens.first().map(|token| token.file).unwrap_or_default();
+    let mut scanner = Scanner::resume_at(std::mem::take(source), file, position, line, col);
+    let mut scanned = Vec::new();
+    let mut errors = Vec::new();
+    let mut resync = None;
+
+    loop {
+        let clean = !scanner.in_interpolation();
+        let token = match scanner.next() {
+            Some(Ok(token)) => token,
+            Some(Err(error)) => {
+                errors.push(error);
+                continue;
+            }
+            None => break,
+        };
+
+        if clean && errors.is_empty() && token.span.start >= edit_end {
+            let old_start = (token.span.start as isize - delta) as usize;
+            let old = tokens.partition_point(|token| token.span.start < old_start);
+            if tokens.get(old).is_some_and(|old_token| {
+                old_token.span.start == old_start
+                    && depths[old] == 0
+                    && old_token.ty == token.ty
+                    && old_token.lexeme == token.lexeme
+            }) {
+                resync = Some((old, token));
+                break;
+            }
+        }
+        let is_eof = token.ty == TokenType::Eof;
+        scanned.push(token);
+        if is_eof {
+            break;
+        }
+    }
+    *source = std::mem::take(&mut scanner.source);
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let scanned_len = scanned.len();
+    let (kept_from, shift) = match resync {
+        Some((old, new_token)) => {
+            let old_token = &tokens[old];
+            let shift = Shift {
+                bytes: delta,
+                lines: new_token.line as isize - old_token.line as isize,
+                line: old_token.line,
+                cols: new_token.col as i64 - old_token.col as i64,
+            };
+            for token in &mut tokens[old..] {
+                (token.line, token.col) = shift.position(token.line, token.col);
+                token.span = shift.span(token.span);
+            }
+            (old, shift)
+        }
+        None => (tokens.len(), Shift::default()),
+    };
+    tokens.splice(restart..kept_from, scanned);
+    Ok((restart..restart + scanned_len, shift))
+}
+
+/// How many string interpolations are open at the start of each token.
+fn interpolation_depths(tokens: &[Token]) -> Vec<usize> {
+    let mut depth = 0;
+    tokens
+        .iter()
+        .map(|token| {
+            let before = depth;
+            match token.ty {
+                TokenType::InterpolationStart => depth += 1,
+                TokenType::InterpolationEnd => depth -= 1,
+                _ => (),
+            }
+            before
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_edit(source: &str, edit: TextEdit) {
+        let mut source = source.to_string();
+        let mut tokens = Scanner::new(source.clone()).scan_tokens().unwrap();
+
+        let result = relex(&mut source, &mut tokens, &edit);
+        match Scanner::new(source.clone()).scan_tokens() {
+            Ok(expected) => {
+                result.unwrap();
+                assert_eq!(format!("{:?}", tokens), format!("{:?}", expected));
+            }
+            Err(expected) => assert_eq!(result.unwrap_err(), expected),
+        }
+    }
+
+    #[test]
+    fn test_relex_matches_full_scan() {
+        let source = "var a = 1;\nprint \"x ${a + 2} y\";\n/* c */ var bc = a >= 10; // end\n";
+        check_edit(source, TextEdit::new(4..5, "abc"));
+        check_edit(source, TextEdit::new(5..5, "d"));
+        check_edit(source, TextEdit::new(0..0, "\n\n"));
+        check_edit(source, TextEdit::new(9..11, ""));
+        check_edit(source, TextEdit::new(20..21, "(a)"));
+        check_edit(source, TextEdit::new(36..37, "*"));
+        check_edit(source, TextEdit::new(36..36, "/*"));
+        check_edit(source, TextEdit::new(source.len()..source.len(), "x"));
+        check_edit(source, TextEdit::new(0..source.len(), "nil"));
+    }
+
+    #[test]
+    fn test_relex_reports_errors_in_the_edit() {
+        check_edit("a = \"b\";\nc;", TextEdit::new(6..8, ";"));
+    }
+
+    #[test]
+    fn test_relex_only_scans_near_the_edit() {
+        let mut source = "var a = 1;\n".repeat(100);
+        let mut tokens = Scanner::new(source.clone()).scan_tokens().unwrap();
+
+        let rescanned = relex(&mut source, &mut tokens, &TextEdit::new(499..500, "bb")).unwrap();
+
+        assert!(rescanned.len() <= 3, "rescanned {:?}", rescanned);
+        assert_eq!(tokens[rescanned.start + 1].lexeme, "bb");
+        let expected = Scanner::new(source).scan_tokens().unwrap();
+        assert_eq!(format!("{:?}", tokens), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_random_edits() {
+        let mut source = String::from(
+            "var ñ = \"a ${b + \"c${d}\"} e\";\nfun f(x) { return x * 0x1F; }\n/* x /* y */ */ print f(ñ);\n",
+        );
+        let alphabet = [
+            "a", " ", "\n", "\"", "${", "}", "/*", "*/", "//", "1", ".", "ñ", "=", "",
+        ];
+        let mut seed: u64 = 42;
+        let mut random = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound.max(1)
+        };
+
+        for _ in 0..500 {
+            let mut start = random(source.len() + 1);
+            while !source.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = (start + random(4)).min(source.len());
+            while !source.is_char_boundary(end) {
+                end += 1;
+            }
+            let edit = TextEdit::new(start..end, alphabet[random(alphabet.len())]);
+
+            let Ok(mut tokens) = Scanner::new(source.clone()).scan_tokens() else {
+                source.replace_range(edit.range.clone(), &edit.replacement);
+                continue;
+            };
+            let result = relex(&mut source, &mut tokens, &edit);
+            let expected = Scanner::new(source.clone()).scan_tokens();
+            match (result, expected) {
+                (Ok(_), Ok(expected)) => {
+                    assert_eq!(
+                        format!("{:?}", tokens),
+                        format!("{:?}", expected),
+                        "{:?}",
+                        edit
+                    )
+                }
+                (Err(errors), Err(expected)) => assert_eq!(errors, expected, "{:?}", edit),
+                (result, expected) => {
+                    panic!("{:?} gave {:?}, expected {:?}", edit, result, expected)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::io::{self, Read};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads UTF-8 text from an `io::Read` in chunks. A char split between two reads is held back
+/// until the rest of its bytes arrive.
+pub struct ChunkReader {
+    reader: Box<dyn Read>,
+    pending: Vec<u8>,
+    chunk_size: usize,
+}
+
+impl ChunkReader {
+    pub fn new(reader: impl Read + 'static) -> Self {
+        ChunkReader::with_chunk_size(reader, CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(reader: impl Read + 'static, chunk_size: usize) -> Self {
+        ChunkReader {
+            reader: Box::new(reader),
+            pending: Vec::new(),
+            chunk_size,
+        }
+    }
+
+    /// Appends at least one more char to `out`, or returns `Ok(false)` at the end of the input.
+    /// Invalid UTF-8 is appended as U+FFFD and reported with an `InvalidData` error; reading
+    /// can carry on after it.
+    pub fn read_into(&mut self, out: &mut String) -> io::Result<bool> {
+        let mut chunk = vec![0; self.chunk_size];
+        loop {
+            if !self.pending.is_empty() {
+                match std::str::from_utf8(&self.pending) {
+                    Ok(text) => {
+                        out.push_str(text);
+                        self.pending.clear();
+                        return Ok(true);
+                    }
+                    Err(e) if e.valid_up_to() > 0 => {
+                        let valid = e.valid_up_to();
+                        // `valid_up_to` marks the end of the valid UTF-8 prefix.
+                        out.push_str(std::str::from_utf8(&self.pending[..valid]).unwrap());
+                        self.pending.drain(..valid);
+                        return Ok(true);
+                    }
+                    Err(e) => {
+                        if let Some(len) = e.error_len() {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..len);
+                            return Err(invalid_utf8());
+                        }
+                        // Otherwise the pending bytes are the start of a char we haven't
+                        // fully read yet.
+                    }
+                }
+            }
+
+            let read = match self.reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            if read == 0 {
+                if self.pending.is_empty() {
+                    return Ok(false);
+                }
+                self.pending.clear();
+                out.push(char::REPLACEMENT_CHARACTER);
+                return Err(invalid_utf8());
+            }
+            self.pending.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "input is not valid UTF-8")
+}
+
+impl fmt::Debug for ChunkReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChunkReader")
+            .field("pending", &self.pending)
+            .field("chunk_size", &self.chunk_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chars_split_across_chunks() {
+        let mut reader = ChunkReader::with_chunk_size("añ🦤".as_bytes(), 1);
+        let mut out = String::new();
+        while reader.read_into(&mut out).unwrap() {}
+        assert_eq!(out, "añ🦤");
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_replaced() {
+        let mut reader = ChunkReader::new(&b"a\xffb\xf0\x9f"[..]);
+        let mut out = String::new();
+        assert!(reader.read_into(&mut out).unwrap());
+        assert_eq!(
+            reader.read_into(&mut out).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(reader.read_into(&mut out).unwrap());
+        assert_eq!(
+            reader.read_into(&mut out).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(!reader.read_into(&mut out).unwrap());
+        assert_eq!(out, "a\u{FFFD}b\u{FFFD}");
+    }
+}
@@ -1,3 +1,10 @@
+pub mod error;
+pub mod incremental;
+pub mod input;
+#[allow(clippy::module_inception)]
 pub mod scanner;
+pub mod source;
+pub mod symbol;
 pub mod token;
 pub mod tokentype;
+pub mod trivia;
@@ -1,218 +1,806 @@
 use super::{
+    error::{ScanError, ScanWarning},
+    input::ChunkReader,
+    source::{FileId, Span},
+    symbol::Symbol,
     token::Token,
     tokentype::{Literal, TokenType},
+    trivia::{self, LosslessToken},
 };
-use core::panic;
-use std::collections::HashMap;
-use std::{char, str::Chars, u32, usize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Read};
+use tracing::{debug, trace};
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{confusable_detection::skeleton, MixedScript};
 
+/// An open `${` inside a string, with the `{`/`}` nesting seen inside its expression.
+#[derive(Debug)]
+struct Interpolation {
+    braces: usize,
+    /// Where the string holding the `${` starts in the whole input.
+    start: usize,
+    line: usize,
+    col: usize,
+}
+
+const KEYWORDS: [(&str, TokenType); 18] = [
+    ("and", TokenType::And),
+    ("break", TokenType::Break),
+    ("class", TokenType::Class),
+    ("continue", TokenType::Continue),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
+/// `KEYWORDS` laid out by `keyword_hash`, which has no collisions for them. Adding a keyword
+/// that collides fails to compile; pick new constants for the hash if that happens.
+static KEYWORD_TABLE: [Option<(&str, TokenType)>; 32] = keyword_table();
+
+const fn keyword_hash(text: &[u8]) -> usize {
+    (text.len() + 7 * text[0] as usize + text[text.len() - 1] as usize) % 32
+}
+
+const fn keyword_table() -> [Option<(&'static str, TokenType)>; 32] {
+    let mut table = [None; 32];
+    let mut i = 0;
+    while i < KEYWORDS.len() {
+        let slot = keyword_hash(KEYWORDS[i].0.as_bytes());
+        assert!(table[slot].is_none(), "keyword hash collision");
+        table[slot] = Some(KEYWORDS[i]);
+        i += 1;
+    }
+    table
+}
+
+/// How much already scanned text a reader-backed scanner keeps before dropping it.
+const DISCARD_THRESHOLD: usize = 4 * 1024;
+
+/// `start` and `current` are byte offsets into `source`, so slicing the lexeme out is always on
+/// a char boundary. Lines and columns are tracked as we advance instead of being recomputed.
+///
+/// A scanner is an iterator of tokens and errors that always finishes with an `Eof` token.
+/// Scanners built with `from_reader` pull their input in chunks as tokens are asked for, and
+/// `source` then only holds the part of the input that hasn't been discarded yet; `offset` is
+/// where that part starts in the whole input.
 #[derive(Debug)]
 pub struct Scanner {
     pub source: String,
-    pub tokens: Vec<Token>,
-    start: u32,
-    current: u32,
+    pub file: FileId,
+    pub tokens: VecDeque<Token>,
+    pub errors: VecDeque<ScanError>,
+    pub warnings: Vec<ScanWarning>,
+    input: Option<ChunkReader>,
+    retain_source: bool,
+    offset: usize,
+    finished: bool,
+    start: usize,
+    current: usize,
     line: usize,
+    col: usize,
+    start_line: usize,
+    start_col: usize,
+    interpolations: Vec<Interpolation>,
+    /// Identifiers already checked for confusables, and the first one seen with each skeleton.
+    checked_identifiers: HashSet<Symbol>,
+    skeletons: HashMap<String, Symbol>,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
+        Scanner::with_file(source, FileId::default())
+    }
+
+    /// A scanner whose tokens point back to `file` in a `SourceMap`.
+    pub fn with_file(source: String, file: FileId) -> Self {
         Scanner {
             source,
-            tokens: Vec::new(),
+            file,
+            tokens: VecDeque::new(),
+            errors: VecDeque::new(),
+            warnings: Vec::new(),
+            input: None,
+            retain_source: true,
+            offset: 0,
+            finished: false,
             start: 0,
             current: 0,
             line: 1,
+            col: 1,
+            start_line: 1,
+            start_col: 1,
+            interpolations: Vec::new(),
+            checked_identifiers: HashSet::new(),
+            skeletons: HashMap::new(),
         }
     }
 
-    fn keywords() -> HashMap<String, TokenType> {
-        let mut keywords = HashMap::new();
-        keywords.insert("and".to_string(), TokenType::And);
-        keywords.insert("class".to_string(), TokenType::Class);
-        keywords.insert("else".to_string(), TokenType::Else);
-        keywords.insert("false".to_string(), TokenType::False);
-        keywords.insert("for".to_string(), TokenType::For);
-        keywords.insert("fun".to_string(), TokenType::Fun);
-        keywords.insert("if".to_string(), TokenType::If);
-        keywords.insert("nil".to_string(), TokenType::Nil);
-        keywords.insert("or".to_string(), TokenType::Or);
-        keywords.insert("print".to_string(), TokenType::Print);
-        keywords.insert("return".to_string(), TokenType::Return);
-        keywords.insert("super".to_string(), TokenType::Super);
-        keywords.insert("this".to_string(), TokenType::This);
-        keywords.insert("true".to_string(), TokenType::True);
-        keywords.insert("var".to_string(), TokenType::Var);
-        keywords.insert("while".to_string(), TokenType::While);
-        keywords
-    }
-
-    pub fn identifier(&mut self, start_position: u32) {
-        while let Some(c) = self.source.chars().nth(self.current as usize) {
-            if c.is_alphanumeric() || c == '_' {
-                self.current += 1;
+    /// A scanner that reads its source lazily from `reader`.
+    pub fn from_reader(reader: impl Read + 'static) -> Self {
+        let mut scanner = Scanner::new(String::new());
+        scanner.input = Some(ChunkReader::new(reader));
+        scanner.retain_source = false;
+        scanner
+    }
+
+    /// A scanner that starts at byte `position` of `source`, which must be the start of a token
+    /// scanned with no string interpolation open, on the given line and column.
+    pub fn resume_at(
+        source: String,
+        file: FileId,
+        position: usize,
+        line: usize,
+        col: usize,
+    ) -> Self {
+        let mut scanner = Scanner::with_file(source, file);
+        scanner.current = position;
+        scanner.line = line;
+        scanner.col = col;
+        scanner
+    }
+
+    /// True while some `${` is still waiting for its `}`.
+    pub fn in_interpolation(&self) -> bool {
+        !self.interpolations.is_empty()
+    }
+
+    /// Looks `text` up in the keyword table: one hash and at most one string comparison.
+    fn keyword(text: &str) -> Option<TokenType> {
+        let bytes = text.as_bytes();
+        if bytes.is_empty() {
+            return None;
+        }
+        match KEYWORD_TABLE[keyword_hash(bytes)] {
+            Some((keyword, token_type)) if keyword == text => Some(token_type),
+            _ => None,
+        }
+    }
+
+    /// Identifiers follow UAX #31: an `XID_Start` char or `_`, then `XID_Continue` chars. They
+    /// are interned in NFC, so names typed with combining accents or precomposed letters are the
+    /// same variable; the token's lexeme is still the text as written.
+    pub fn identifier(&mut self) {
+        while let Some(c) = self.peek() {
+            if unicode_ident::is_xid_continue(c) {
+                self.advance();
             } else {
                 break;
             }
         }
 
-        let text = &self.source[start_position as usize..self.current as usize];
+        let text = &self.source[self.start..self.current];
+        if let Some(token_type) = Scanner::keyword(text) {
+            self.add_token(token_type, None);
+            return;
+        }
+
+        let symbol = if text.is_ascii() || unicode_normalization::is_nfc(text) {
+            Symbol::intern(text)
+        } else {
+            Symbol::intern(&text.nfc().collect::<String>())
+        };
+        if self.checked_identifiers.insert(symbol) {
+            self.check_identifier(symbol);
+        }
+        self.add_token(TokenType::Identifier, Some(Literal::Identifier(symbol)));
+    }
 
-        let token_type = Scanner::keywords()
-            .get(text) // Try to find the keyword in the keywords map
-            .cloned() // Clone the token type if found
-            .unwrap_or(TokenType::Identifier); // Default to Identifier if not a keyword
+    /// Warns about an identifier that mixes scripts or that can be mistaken for another one in
+    /// the same source, like a Cyrillic `а` for a Latin `a`. Names that only use ASCII are never
+    /// confused with each other.
+    fn check_identifier(&mut self, symbol: Symbol) {
+        let name = symbol.as_str();
+        if !name.is_ascii() && !name.is_single_script() {
+            let span = self.span_from(self.start);
+            self.warnings.push(
+                ScanWarning::new(
+                    self.start_line,
+                    self.start_col,
+                    format!(
+                        "Identifier '{}' mixes characters from different scripts.",
+                        name
+                    ),
+                )
+                .at(self.file, span),
+            );
+        }
 
-        // Add the token (either a keyword or an identifier)
-        self.add_token(token_type, text.to_string(), None);
+        let first = *self
+            .skeletons
+            .entry(skeleton(name).collect())
+            .or_insert(symbol);
+        if first != symbol && !(name.is_ascii() && first.as_str().is_ascii()) {
+            let span = self.span_from(self.start);
+            self.warnings.push(
+                ScanWarning::new(
+                    self.start_line,
+                    self.start_col,
+                    format!("Identifier '{}' looks like '{}'.", name, first),
+                )
+                .at(self.file, span),
+            );
+        }
     }
 
-    pub fn number(&mut self, start_position: u32) {
-        while let Some(c) = self.source.chars().nth(self.current as usize) {
-            if !c.is_digit(10) {
-                if c == '\n' {
-                    panic!("You cant have multiline numbers")
+    /// Numbers are decimal (`12`, `3.14`, `1e9`, `2.5E-3`), hexadecimal (`0xFF`) or binary
+    /// (`0b1010`). Any of them can use `_` between digits as a separator.
+    pub fn number(&mut self, first: char) {
+        if first == '0' {
+            if self.match_char('x') || self.match_char('X') {
+                return self.radix_number(16, "hexadecimal");
+            }
+            if self.match_char('b') || self.match_char('B') {
+                return self.radix_number(2, "binary");
+            }
+        }
+
+        // The first digit has already been consumed.
+        self.digits(10, true);
+
+        if self.match_char('.') && !self.digits(10, false) {
+            let number = self.source[self.start..self.current].to_string();
+            return self.error(format!(
+                "Malformed number literal '{}': expected digits after '.'.",
+                number
+            ));
+        }
+
+        if self.match_char('e') || self.match_char('E') {
+            if !self.match_char('+') {
+                self.match_char('-');
+            }
+            if !self.digits(10, false) {
+                let number = self.source[self.start..self.current].to_string();
+                return self.error(format!(
+                    "Malformed number literal '{}': expected digits in the exponent.",
+                    number
+                ));
+            }
+        }
+
+        let number: String = self.source[self.start..self.current]
+            .chars()
+            .filter(|c| *c != '_')
+            .collect();
+        match number.parse::<f64>() {
+            Ok(value) => self.add_token(TokenType::Number, Some(Literal::Number(value))),
+            Err(_) => self.error(format!("Invalid number literal '{}'.", number)),
+        }
+    }
+
+    fn radix_number(&mut self, radix: u32, name: &str) {
+        let digits_start = self.current;
+        if !self.digits(radix, false) {
+            let prefix = self.source[self.start..self.current].to_string();
+            return self.error(format!(
+                "Malformed number literal '{}': expected {} digits.",
+                prefix, name
+            ));
+        }
+
+        if let Some(c) = self.peek().filter(|c| c.is_alphanumeric()) {
+            self.error_here(format!("Invalid digit '{}' in {} literal.", c, name));
+            while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                self.advance();
+            }
+            return;
+        }
+
+        let value = self.source[digits_start..self.current]
+            .chars()
+            .filter_map(|c| c.to_digit(radix))
+            .fold(0.0, |value, digit| value * radix as f64 + digit as f64);
+        self.add_token(TokenType::Number, Some(Literal::Number(value)));
+    }
+
+    /// Consumes a run of digits in `radix` with optional `_` separators and returns whether any
+    /// digit was consumed. `after_digit` tells if the character right before was a digit.
+    fn digits(&mut self, radix: u32, mut after_digit: bool) -> bool {
+        let mut any = false;
+        loop {
+            match self.peek() {
+                Some(c) if c.is_digit(radix) => {
+                    self.advance();
+                    after_digit = true;
+                    any = true;
                 }
-                break;
+                Some('_') => {
+                    let (start, line, col) = (self.current, self.line, self.col);
+                    while self.match_char('_') {}
+                    if !after_digit || !self.peek().is_some_and(|c| c.is_digit(radix)) {
+                        self.error_from(
+                            start,
+                            line,
+                            col,
+                            "Digit separator '_' must be between digits.",
+                        );
+                    }
+                    after_digit = false;
+                }
+                _ => return any,
             }
-            self.current += 1;
         }
+    }
 
-        if self.current == self.source.len() as u32 {
-            panic!("Hey you just did something funny, unnterminated number literal")
-        } else {
-            self.current -= 1;
-            let number = &self.source[start_position as usize..(self.current + 1) as usize];
-            self.add_token(
-                TokenType::Number,
-                "number".to_string(),
-                Some(Literal::Number(
-                    number.parse::<f64>().expect("This should wokr!"),
-                )),
-            );
+    /// A `"..."` string understands escape sequences. `"""..."""` and `r"..."` strings are
+    /// taken as written, so they need no escaping.
+    pub fn string(&mut self) {
+        if self.peek() == Some('"') && self.peek_next() == Some('"') {
+            self.advance();
+            self.advance();
+            return self.triple_quoted_string();
         }
+
+        self.string_segment(false);
     }
-    pub fn string(&mut self, start_position: u32) {
-        self.current += 1;
-        while let Some(c) = self.source.chars().nth(self.current as usize) {
-            if c == '"' {
-                break;
+
+    /// Scans string contents up to the closing quote or the next `${`. `continued` is true when
+    /// resuming after the `}` of an interpolated expression, so the segment is the middle or end
+    /// of an interpolated string rather than a plain string or its start.
+    fn string_segment(&mut self, continued: bool) {
+        let errors_before = self.errors.len();
+        let mut value = String::new();
+        let token_type = loop {
+            match self.advance() {
+                Some('"') if continued => break TokenType::InterpolationEnd,
+                Some('"') => break TokenType::String,
+                Some('$') if self.match_char('{') => {
+                    self.interpolations.push(Interpolation {
+                        braces: 0,
+                        start: self.offset + self.start,
+                        line: self.start_line,
+                        col: self.start_col,
+                    });
+                    if continued {
+                        break TokenType::InterpolationMiddle;
+                    }
+                    break TokenType::InterpolationStart;
+                }
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                Some(c) => value.push(c),
+                None => return self.error("Unterminated string.".to_string()),
             }
+        };
 
-            if c == '\n' {
-                self.line += 1;
+        if self.errors.len() == errors_before {
+            self.add_token(token_type, Some(Literal::Str(value)));
+        }
+    }
+
+    pub fn raw_string(&mut self) {
+        while self.peek().is_some_and(|c| c != '"') {
+            self.advance();
+        }
+
+        if !self.match_char('"') {
+            return self.error("Unterminated raw string.".to_string());
+        }
+        let value = self.source[self.start + 2..self.current - 1].to_string();
+        self.add_token(TokenType::String, Some(Literal::Str(value)));
+    }
+
+    fn triple_quoted_string(&mut self) {
+        let value_start = self.current;
+        loop {
+            self.fill(3);
+            if self.source[self.current..].starts_with("\"\"\"") {
+                break;
             }
+            if self.advance().is_none() {
+                return self.error("Unterminated triple-quoted string.".to_string());
+            }
+        }
 
-            self.current += 1;
+        let value = self.source[value_start..self.current].to_string();
+        for _ in 0..3 {
+            self.advance();
         }
+        self.add_token(TokenType::String, Some(Literal::Str(value)));
+    }
 
-        if self.current >= self.source.len() as u32 {
-            panic!("Unterminated string literal");
-        } else {
-            self.current += 1;
-            println!("{:?}", start_position);
-            println!("{:?}", self.current);
+    /// Scans what follows a `\\` inside a string. Invalid escapes are reported at the
+    /// character that makes them invalid and give back `None`.
+    fn escape(&mut self) -> Option<char> {
+        let (start, line, col) = (self.current, self.line, self.col);
+        let escaped = match self.peek()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            '$' => '$',
+            'u' => {
+                self.advance();
+                return self.unicode_escape();
+            }
+            c => {
+                self.advance();
+                self.error_from(
+                    start,
+                    line,
+                    col,
+                    format!("Invalid escape sequence '\\{}'.", c.escape_default()),
+                );
+                return None;
+            }
+        };
+        self.advance();
+        Some(escaped)
+    }
 
-            let literal_value =
-                self.source[(start_position + 1) as usize..(self.current - 1) as usize].to_string();
+    /// `\u{...}` with one to six hex digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.match_char('{') {
+            self.error_here("Expected '{' after '\\u'.".to_string());
+            return None;
+        }
 
-            self.add_token(
-                TokenType::String,
-                "".to_string(),
-                Some(Literal::Str(literal_value)),
+        let (start, line, col) = (self.current, self.line, self.col);
+        let mut value: u32 = 0;
+        let mut digits = 0;
+        loop {
+            match self.peek() {
+                Some('}') => break,
+                Some(c) if c.is_ascii_hexdigit() => {
+                    if digits == 6 {
+                        self.error_here("Unicode escape has more than 6 hex digits.".to_string());
+                        return None;
+                    }
+                    self.advance();
+                    value = value * 16 + c.to_digit(16).unwrap_or_default();
+                    digits += 1;
+                }
+                _ => {
+                    self.error_here("Expected hex digit or '}' in Unicode escape.".to_string());
+                    return None;
+                }
+            }
+        }
+        self.advance();
+
+        if digits == 0 {
+            self.error_from(start, line, col, "Empty Unicode escape.");
+            return None;
+        }
+        let c = char::from_u32(value);
+        if c.is_none() {
+            self.error_from(
+                start,
+                line,
+                col,
+                format!("'{:X}' is not a valid Unicode scalar value.", value),
             );
         }
+        c
     }
 
-    pub fn match_char(&mut self, expected: char) -> bool {
-        if self.current >= self.source.len() as u32 {
+    pub fn block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some('/') if self.match_char('*') => depth += 1,
+                Some('*') if self.match_char('/') => depth -= 1,
+                Some(_) => (),
+                None => {
+                    self.error("Unterminated block comment.".to_string());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Makes sure at least `bytes` bytes after `current` are buffered, unless the input ends
+    /// first.
+    fn fill(&mut self, bytes: usize) {
+        while self.source.len() - self.current < bytes && self.read_more() {}
+    }
+
+    fn read_more(&mut self) -> bool {
+        let Some(input) = self.input.as_mut() else {
             return false;
+        };
+        let before = self.source.len();
+        match input.read_into(&mut self.source) {
+            Ok(true) => {
+                trace!(target: "scanner", bytes = self.source.len() - before, "read input");
+                true
+            }
+            Ok(false) => {
+                self.input = None;
+                false
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                self.error_here("Input is not valid UTF-8.".to_string());
+                true
+            }
+            Err(e) => {
+                self.error_here(format!("Could not read input: {}.", e));
+                self.input = None;
+                false
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        if self.current >= self.source.len() {
+            self.fill(1);
+        }
+        self.source[self.current..].chars().next()
+    }
+
+    fn peek_next(&mut self) -> Option<char> {
+        let first = self.peek()?;
+        self.fill(first.len_utf8() + 1);
+        self.source[self.current..].chars().nth(1)
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.current += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
         }
-        if self.source.chars().nth((self.current + 1) as usize) != Some(expected) {
+        Some(c)
+    }
+
+    pub fn match_char(&mut self, expected: char) -> bool {
+        if self.peek() != Some(expected) {
             return false;
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
-    pub fn add_token(&mut self, token_type: TokenType, lexeme: String, literal: Option<Literal>) {
-        let token_to_add = Token::new(token_type, lexeme, literal, self.line, self.start);
-        self.tokens.push(token_to_add)
+    /// Records an error at the start of the token being scanned.
+    fn error(&mut self, message: String) {
+        self.error_from(self.start, self.start_line, self.start_col, message);
+    }
+
+    /// Records an error at the character about to be scanned.
+    fn error_here(&mut self, message: String) {
+        let end = self.current + self.peek().map_or(0, char::len_utf8);
+        let span = Span::new(self.offset + self.current, self.offset + end);
+        self.errors
+            .push_back(ScanError::new(self.line, self.col, message).at(self.file, span));
+    }
+
+    /// Records an error covering the source from `start`, on `line` and `col`, to the current
+    /// position.
+    fn error_from(&mut self, start: usize, line: usize, col: usize, message: impl Into<String>) {
+        let span = self.span_from(start);
+        self.errors
+            .push_back(ScanError::new(line, col, message).at(self.file, span));
+    }
+
+    /// The span from `start` in `source` to the current position, as offsets in the whole input.
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(self.offset + start, self.offset + self.current)
+    }
+
+    pub fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
+        // Only literals need their own copy of the source text.
+        let lexeme = match (&literal, token_type.lexeme()) {
+            (Some(Literal::Identifier(symbol)), _)
+                if symbol.as_str() == &self.source[self.start..self.current] =>
+            {
+                Cow::Borrowed(symbol.as_str())
+            }
+            (_, Some(fixed)) => Cow::Borrowed(fixed),
+            _ => Cow::Owned(self.source[self.start..self.current].to_string()),
+        };
+        let token_to_add = Token::new(
+            token_type,
+            lexeme,
+            literal,
+            self.file,
+            self.span_from(self.start),
+            self.start_line,
+            self.start_col as u32,
+        );
+        trace!(
+            target: "scanner",
+            ty = %token_to_add.ty,
+            lexeme = %token_to_add.lexeme,
+            line = token_to_add.line,
+            col = token_to_add.col,
+            "token"
+        );
+        self.tokens.push_back(token_to_add)
+    }
+
+    /// Scans the whole source. Scanning does not stop at the first lexical error, so the
+    /// `Err` side holds every problem found in the file.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<ScanError>> {
+        while !self.finished {
+            self.scan_next();
+        }
+        debug!(
+            target: "scanner",
+            tokens = self.tokens.len(),
+            errors = self.errors.len(),
+            warnings = self.warnings.len(),
+            "scanned source"
+        );
+
+        if self.errors.is_empty() {
+            Ok(std::mem::take(&mut self.tokens).into())
+        } else {
+            Err(std::mem::take(&mut self.errors).into())
+        }
+    }
+
+    /// Like `scan_tokens`, but keeps whitespace, newlines and comments as trivia on the tokens
+    /// around them. The `Eof` token holds whatever trivia ends the file.
+    pub fn scan_lossless(&mut self) -> Result<Vec<LosslessToken>, Vec<ScanError>> {
+        self.retain_source = true;
+        let tokens = self.scan_tokens()?;
+        Ok(trivia::attach_trivia(&self.source, tokens))
     }
 
-    pub fn scan_tokens(&mut self) {
-        while let Some(c) = self.source.chars().nth(self.current as usize) {
-            println!("I read c{} in this {:?}", c, self.current);
+    /// Scans the next token, or finishes the stream with `Eof` once the input runs out.
+    fn scan_next(&mut self) {
+        if !self.retain_source && self.current > DISCARD_THRESHOLD {
+            self.source.drain(..self.current);
+            self.offset += self.current;
+            self.current = 0;
+        }
+
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_col = self.col;
+        if let Some(c) = self.advance() {
             self.scan_token(c);
-            self.current += 1;
-            self.start += 1;
+            return;
         }
+
+        for interpolation in std::mem::take(&mut self.interpolations) {
+            let span = Span::new(interpolation.start, self.offset + self.current);
+            self.errors.push_back(
+                ScanError::new(
+                    interpolation.line,
+                    interpolation.col,
+                    "Unterminated string interpolation.",
+                )
+                .at(self.file, span),
+            );
+        }
+        self.add_token(TokenType::Eof, None);
+        self.finished = true;
     }
 
     pub fn scan_token(&mut self, c: char) {
         match c {
-            '(' => self.add_token(TokenType::LeftParen, "(".to_string(), None),
-            ')' => self.add_token(TokenType::RightParen, ")".to_string(), None),
-            '{' => self.add_token(TokenType::LeftBrace, "{".to_string(), None),
-            '}' => self.add_token(TokenType::RightBrace, "}".to_string(), None),
-            ',' => self.add_token(TokenType::Comma, ",".to_string(), None),
-            '.' => self.add_token(TokenType::Dot, ".".to_string(), None),
-            '-' => self.add_token(TokenType::Minus, "-".to_string(), None),
-            '+' => self.add_token(TokenType::Plus, "+".to_string(), None),
-            ';' => self.add_token(TokenType::Semicolon, ";".to_string(), None),
-            '*' => self.add_token(TokenType::Star, "*".to_string(), None),
+            '(' => self.add_token(TokenType::LeftParen, None),
+            ')' => self.add_token(TokenType::RightParen, None),
+            '{' => {
+                if let Some(interpolation) = self.interpolations.last_mut() {
+                    interpolation.braces += 1;
+                }
+                self.add_token(TokenType::LeftBrace, None)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(interpolation) if interpolation.braces == 0 => {
+                    self.interpolations.pop();
+                    self.string_segment(true);
+                }
+                Some(interpolation) => {
+                    interpolation.braces -= 1;
+                    self.add_token(TokenType::RightBrace, None)
+                }
+                None => self.add_token(TokenType::RightBrace, None),
+            },
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
+            ',' => self.add_token(TokenType::Comma, None),
+            '.' => self.add_token(TokenType::Dot, None),
+            '-' => self.add_token(TokenType::Minus, None),
+            '+' => self.add_token(TokenType::Plus, None),
+            ';' => self.add_token(TokenType::Semicolon, None),
+            ':' => self.add_token(TokenType::Colon, None),
+            '?' => self.add_token(TokenType::Question, None),
+            '*' => self.add_token(TokenType::Star, None),
+            '/' => {
+                if self.match_char('/') {
+                    // A line comment goes until the end of the line.
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.advance();
+                    }
+                } else if self.match_char('*') {
+                    self.block_comment();
+                } else {
+                    self.add_token(TokenType::Slash, None);
+                }
+            }
             '!' => {
                 if self.match_char('=') {
-                    self.add_token(TokenType::BangEqual, "!=".to_string(), None);
+                    self.add_token(TokenType::BangEqual, None);
                 } else {
-                    self.add_token(TokenType::Bang, "!".to_string(), None);
+                    self.add_token(TokenType::Bang, None);
                 }
             }
             '=' => {
                 if self.match_char('=') {
-                    self.add_token(TokenType::EqualEqual, "==".to_string(), None);
+                    self.add_token(TokenType::EqualEqual, None);
+                } else if self.match_char('>') {
+                    self.add_token(TokenType::Lambda, None);
                 } else {
-                    self.add_token(TokenType::Equal, "=".to_string(), None);
+                    self.add_token(TokenType::Equal, None);
                 }
             }
             '<' => {
                 if self.match_char('=') {
-                    self.add_token(TokenType::LessEqual, "<=".to_string(), None);
+                    self.add_token(TokenType::LessEqual, None);
                 } else {
-                    self.add_token(TokenType::Less, "<".to_string(), None);
+                    self.add_token(TokenType::Less, None);
                 }
             }
             '>' => {
                 if self.match_char('=') {
-                    self.add_token(TokenType::GreaterEqual, ">=".to_string(), None);
+                    self.add_token(TokenType::GreaterEqual, None);
                 } else {
-                    self.add_token(TokenType::Greater, ">".to_string(), None);
+                    self.add_token(TokenType::Greater, None);
                 }
             }
-            '"' => self.string(self.current),
+            '"' => self.string(),
 
-            '\n' => {
-                self.line += 1;
-                self.start = 0;
-            }
+            'r' if self.match_char('"') => self.raw_string(),
 
-            c if c.is_digit(10) => self.number(self.current),
+            c if c.is_ascii_digit() => self.number(c),
 
-            c if c.is_alphabetic() => self.identifier(self.current),
+            c if c == '_' || unicode_ident::is_xid_start(c) => self.identifier(),
 
             // Ignore whitespace
-            ' ' | '\r' | '\t' => (),
+            ' ' | '\r' | '\t' | '\n' => (),
 
-            _ => (),
+            c => self.error(format!("Unexpected character '{}'.", c)),
         };
     }
 }
 
+impl Iterator for Scanner {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(error) = self.errors.pop_front() {
+                return Some(Err(error));
+            }
+            if let Some(token) = self.tokens.pop_front() {
+                return Some(Ok(token));
+            }
+            if self.finished {
+                return None;
+            }
+            self.scan_next();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::scanner::token;
-
     use super::*;
+    use crate::scanner::source::SourceMap;
 
     #[test]
     fn test_scanner_new() {
@@ -226,17 +814,17 @@ mod tests {
     fn test_scaner_scan() {
         let source_code = String::from("let x = (8;");
         let mut scanner = Scanner::new(source_code.clone());
-        scanner.scan_tokens();
-        assert!(!scanner.tokens.is_empty());
+        let tokens = scanner.scan_tokens().unwrap();
+        assert!(!tokens.is_empty());
     }
 
     #[test]
     fn test_scanner_handles_newline() {
         let source_code = String::from("( \n )");
         let mut scanner = Scanner::new(source_code.clone());
-        scanner.scan_tokens();
+        let tokens = scanner.scan_tokens().unwrap();
 
-        assert_eq!(scanner.tokens.len(), 2);
+        assert_eq!(tokens.len(), 3);
 
         assert_eq!(scanner.line, 2);
     }
@@ -245,64 +833,570 @@ mod tests {
     fn test_special_chars() {
         let source_code = String::from("( \n )");
         let mut scanner = Scanner::new(source_code.clone());
-        scanner.scan_tokens();
+        assert!(scanner.scan_tokens().is_ok());
     }
 
     #[test]
     fn test_scanner_handles_string_literal() {
         let source_code = String::from("\"Hola Mundo\"");
         let mut scanner = Scanner::new(source_code.clone());
-        scanner.scan_tokens();
-        assert_eq!(scanner.tokens.len(), 1);
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 2);
     }
 
     #[test]
     fn test_scanner_handles_number_literal() {
         let source_code = String::from("1234 ");
         let mut scanner = Scanner::new(source_code.clone());
-        scanner.scan_tokens();
+        let tokens = scanner.scan_tokens().unwrap();
 
         // Asegúrate de que se detecta correctamente un token de tipo número
-        assert_eq!(scanner.tokens.len(), 1);
+        assert_eq!(tokens.len(), 2);
 
         // Verifica que el tipo de token sea `Number`
 
         // Asegúrate de que el literal sea el valor correcto
-        if let Some(Literal::Number(value)) = &scanner.tokens[0].literal {
+        if let Some(Literal::Number(value)) = &tokens[0].literal {
             assert_eq!(*value, 1234.0);
         }
     }
 
-    // #[test]
-    // #[should_panic(expected = "unnterminated number literal")]
-    // fn test_scanner_handles_unterminated_number_literal() {
-    //     let source_code = String::from("1234\n5678");
-    //     let mut scanner = Scanner::new(source_code.clone());
-    //     scanner.scan_tokens();
-    // }
-    //
-    //
+    #[test]
+    fn test_scanner_handles_number_at_end_of_file() {
+        let source_code = String::from("1234\n5678");
+        let mut scanner = Scanner::new(source_code.clone());
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 3);
+        assert!(matches!(tokens[1].literal, Some(Literal::Number(n)) if n == 5678.0));
+    }
+
+    #[test]
+    fn test_unterminated_string_is_an_error() {
+        let source_code = String::from("(\n  \"Hola\nMundo");
+        let mut scanner = Scanner::new(source_code.clone());
+        let errors = scanner.scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![ScanError::new(2, 3, "Unterminated string.")
+                .at(FileId::default(), Span::new(4, 15))]
+        );
+    }
+
+    #[test]
+    fn test_scanner_reports_every_error() {
+        let source_code = String::from("var a = 1 @\n# b;");
+        let mut scanner = Scanner::new(source_code.clone());
+        let errors = scanner.scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                ScanError::new(1, 11, "Unexpected character '@'.")
+                    .at(FileId::default(), Span::new(10, 11)),
+                ScanError::new(2, 1, "Unexpected character '#'.")
+                    .at(FileId::default(), Span::new(12, 13)),
+            ]
+        );
+    }
 
     #[test]
     fn test_identifier_and_keyword() {
         let source_code = String::from("var myVar = 10;");
         let mut scanner = Scanner::new(source_code.clone());
-        scanner.scan_tokens();
+        let tokens = scanner.scan_tokens().unwrap();
 
-        println!("{:?}", scanner.tokens);
-        assert_eq!(scanner.tokens.len(), 5);
+        println!("{:?}", tokens);
+        assert_eq!(tokens.len(), 6);
 
-        assert_eq!(scanner.tokens[0].ty, TokenType::Var);
+        assert_eq!(tokens[0].ty, TokenType::Var);
 
-        assert_eq!(scanner.tokens[1].ty, TokenType::Identifier);
+        assert_eq!(tokens[1].ty, TokenType::Identifier);
 
-        assert_eq!(scanner.tokens[2].ty, TokenType::Equal);
+        assert_eq!(tokens[2].ty, TokenType::Equal);
 
-        if let Some(Literal::Number(value)) = &scanner.tokens[3].literal {
+        if let Some(Literal::Number(value)) = &tokens[3].literal {
             assert_eq!(*value, 10.0);
         }
 
         // Verify the last token is the semicolon ';'
-        assert_eq!(scanner.tokens[4].ty, TokenType::Semicolon);
+        assert_eq!(tokens[4].ty, TokenType::Semicolon);
+    }
+
+    #[test]
+    fn test_scanner_handles_non_ascii_text() {
+        let source_code = String::from("\"ñandú 🦤\"; año = 1;");
+        let mut scanner = Scanner::new(source_code.clone());
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.ty).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::String,
+                TokenType::Semicolon,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number,
+                TokenType::Semicolon,
+                TokenType::Eof,
+            ]
+        );
+        assert!(matches!(&tokens[0].literal, Some(Literal::Str(s)) if s == "ñandú 🦤"));
+        assert_eq!(tokens[2].lexeme, "año");
+        assert_eq!(tokens[2].col, 12);
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let mut scanner = Scanner::new(String::from("_x ñandú1 Ωμέγα x\u{300}y 日本"));
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme.as_ref()).collect();
+        assert_eq!(
+            lexemes,
+            vec!["_x", "ñandú1", "Ωμέγα", "x\u{300}y", "日本", ""]
+        );
+        assert!(tokens[..5].iter().all(|t| t.ty == TokenType::Identifier));
+
+        let errors = Scanner::new(String::from("a€b \u{300}")).scan_tokens();
+        assert_eq!(
+            errors.unwrap_err(),
+            vec![
+                ScanError::new(1, 2, "Unexpected character '€'.")
+                    .at(FileId::default(), Span::new(1, 4)),
+                ScanError::new(1, 5, "Unexpected character '\u{300}'.")
+                    .at(FileId::default(), Span::new(6, 8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identifiers_are_nfc_normalized() {
+        let source = String::from("cafe\u{301} = café;");
+        let tokens = Scanner::new(source.clone()).scan_tokens().unwrap();
+
+        let symbols: Vec<Symbol> = tokens
+            .iter()
+            .filter_map(|t| match t.literal {
+                Some(Literal::Identifier(symbol)) => Some(symbol),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![Symbol::intern("café"), Symbol::intern("café")]
+        );
+        assert_eq!(tokens[0].lexeme, "cafe\u{301}");
+
+        let lossless = Scanner::new(source.clone()).scan_lossless().unwrap();
+        assert_eq!(trivia::to_source(&lossless), source);
+    }
+
+    #[test]
+    fn test_confusable_identifier_warnings() {
+        let mut scanner = Scanner::new(String::from("var a = 1;\nprint \u{430};\npаypal;"));
+        scanner.scan_tokens().unwrap();
+
+        assert_eq!(
+            scanner.warnings,
+            vec![
+                ScanWarning::new(2, 7, "Identifier 'а' looks like 'a'.")
+                    .at(FileId::default(), Span::new(17, 19)),
+                ScanWarning::new(
+                    3,
+                    1,
+                    "Identifier 'pаypal' mixes characters from different scripts."
+                )
+                .at(FileId::default(), Span::new(21, 28)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spanish_identifiers_do_not_warn() {
+        let mut scanner = Scanner::new(String::from(
+            "var año = 1; var ano = 2; var canción = \"sí\"; var niño_2 = año + ano;",
+        ));
+        scanner.scan_tokens().unwrap();
+        assert!(scanner.warnings.is_empty(), "{:?}", scanner.warnings);
+    }
+
+    #[test]
+    fn test_scanner_does_not_skip_after_token() {
+        let source_code = String::from("\"a\";b;");
+        let mut scanner = Scanner::new(source_code.clone());
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[1].ty, TokenType::Semicolon);
+        assert_eq!(tokens[3].ty, TokenType::Semicolon);
+    }
+
+    #[test]
+    fn test_slash_and_comments() {
+        let source_code = String::from("a / b // c / d\n/* e /* f */\n g */ h");
+        let mut scanner = Scanner::new(source_code.clone());
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.ty).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier,
+                TokenType::Slash,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(tokens[3].lexeme, "h");
+        assert_eq!(tokens[3].line, 3);
+        assert_eq!(tokens[3].col, 7);
+    }
+
+    #[test]
+    fn test_brackets_colon_and_question_mark() {
+        let mut scanner = Scanner::new(String::from("xs[1:-1] = [a?];"));
+        let types: Vec<TokenType> = scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|t| t.ty)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier,
+                TokenType::LeftBracket,
+                TokenType::Number,
+                TokenType::Colon,
+                TokenType::Minus,
+                TokenType::Number,
+                TokenType::RightBracket,
+                TokenType::Equal,
+                TokenType::LeftBracket,
+                TokenType::Identifier,
+                TokenType::Question,
+                TokenType::RightBracket,
+                TokenType::Semicolon,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arrow_token() {
+        let mut scanner = Scanner::new(String::from("(a) => a ==> = >"));
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.ty).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::LeftParen,
+                TokenType::Identifier,
+                TokenType::RightParen,
+                TokenType::Lambda,
+                TokenType::Identifier,
+                TokenType::EqualEqual,
+                TokenType::Greater,
+                TokenType::Equal,
+                TokenType::Greater,
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(tokens[3].lexeme, "=>");
+    }
+
+    #[test]
+    fn test_unterminated_block_comment_is_an_error() {
+        let source_code = String::from("a\n  /* b /* c */\n");
+        let mut scanner = Scanner::new(source_code.clone());
+        let errors = scanner.scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![ScanError::new(2, 3, "Unterminated block comment.")
+                .at(FileId::default(), Span::new(4, 17))]
+        );
+    }
+
+    fn scan_numbers(source: &str) -> Vec<f64> {
+        let mut scanner = Scanner::new(source.to_string());
+        scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .filter(|token| token.ty != TokenType::Eof)
+            .map(|token| match token.literal {
+                Some(Literal::Number(n)) => n,
+                _ => panic!("expected a number, got {:?}", token),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_number_literal_grammar() {
+        assert_eq!(
+            scan_numbers("2.75 1e9 2.5E-3 7e+2 0xFF 0Xff 0b1010 1_000_000 0xff_ff 0"),
+            vec![
+                2.75,
+                1e9,
+                2.5e-3,
+                700.0,
+                255.0,
+                255.0,
+                10.0,
+                1_000_000.0,
+                65535.0,
+                0.0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_number_literals() {
+        let source_code = String::from("1. 0x 2e 0b102 0x_1 3_");
+        let mut scanner = Scanner::new(source_code);
+        let errors = scanner.scan_tokens().unwrap_err();
+
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Malformed number literal '1.': expected digits after '.'.",
+                "Malformed number literal '0x': expected hexadecimal digits.",
+                "Malformed number literal '2e': expected digits in the exponent.",
+                "Invalid digit '2' in binary literal.",
+                "Digit separator '_' must be between digits.",
+                "Digit separator '_' must be between digits.",
+            ]
+        );
+        assert_eq!((errors[3].line, errors[3].col), (1, 14));
+        assert_eq!((errors[4].line, errors[4].col), (1, 18));
+        assert_eq!((errors[5].line, errors[5].col), (1, 22));
+    }
+
+    fn scan_strings(source: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(source.to_string());
+        scanner
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .filter(|token| token.ty != TokenType::Eof)
+            .map(|token| match &token.literal {
+                Some(Literal::Str(s)) => s.clone(),
+                _ => panic!("expected a string, got {:?}", token),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(
+            scan_strings(r#" "a\nb\t\"c\"\\" "\u{1F600}\u{e9}\0" "" "#),
+            vec!["a\nb\t\"c\"\\", "😀é\0", ""]
+        );
+    }
+
+    #[test]
+    fn test_raw_and_triple_quoted_strings() {
+        assert_eq!(
+            scan_strings(r#"r"C:\dir\n" """say "hi" \n""" r"" """""""#),
+            vec![r"C:\dir\n", r#"say "hi" \n"#, "", ""]
+        );
+    }
+
+    #[test]
+    fn test_multi_line_string_starts_at_opening_quote() {
+        let source_code = String::from("(\n   \"\"\"uno\ndos\"\"\" x");
+        let mut scanner = Scanner::new(source_code);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        assert_eq!((tokens[1].line, tokens[1].col), (2, 4));
+        assert!(matches!(&tokens[1].literal, Some(Literal::Str(s)) if s == "uno\ndos"));
+        assert_eq!((tokens[2].line, tokens[2].col), (3, 8));
+    }
+
+    #[test]
+    fn test_invalid_escapes_point_at_offending_character() {
+        let source_code = String::from("\"a\\qb\"\n\"\\u{D800}\" \"\\u{12G}\" \"\\u1\"");
+        let mut scanner = Scanner::new(source_code);
+        let errors = scanner.scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                ScanError::new(1, 4, "Invalid escape sequence '\\q'.")
+                    .at(FileId::default(), Span::new(3, 4)),
+                ScanError::new(2, 5, "'D800' is not a valid Unicode scalar value.")
+                    .at(FileId::default(), Span::new(11, 16)),
+                ScanError::new(2, 18, "Expected hex digit or '}' in Unicode escape.")
+                    .at(FileId::default(), Span::new(24, 25)),
+                ScanError::new(2, 25, "Expected '{' after '\\u'.")
+                    .at(FileId::default(), Span::new(31, 32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_interpolation_tokens() {
+        let source_code = String::from(r#""Hello ${name}, you have ${n + 1} items""#);
+        let mut scanner = Scanner::new(source_code);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.ty).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::InterpolationStart,
+                TokenType::Identifier,
+                TokenType::InterpolationMiddle,
+                TokenType::Identifier,
+                TokenType::Plus,
+                TokenType::Number,
+                TokenType::InterpolationEnd,
+                TokenType::Eof,
+            ]
+        );
+        assert!(matches!(&tokens[0].literal, Some(Literal::Str(s)) if s == "Hello "));
+        assert!(matches!(&tokens[2].literal, Some(Literal::Str(s)) if s == ", you have "));
+        assert!(matches!(&tokens[6].literal, Some(Literal::Str(s)) if s == " items"));
+    }
+
+    #[test]
+    fn test_nested_string_interpolation() {
+        let source_code = String::from(r#""a${ "b${c}" + "{}" }d" "\${x}""#);
+        let mut scanner = Scanner::new(source_code);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.ty).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::InterpolationStart,
+                TokenType::InterpolationStart,
+                TokenType::Identifier,
+                TokenType::InterpolationEnd,
+                TokenType::Plus,
+                TokenType::String,
+                TokenType::InterpolationEnd,
+                TokenType::String,
+                TokenType::Eof,
+            ]
+        );
+        assert!(matches!(&tokens[7].literal, Some(Literal::Str(s)) if s == "${x}"));
+    }
+
+    #[test]
+    fn test_unterminated_interpolation_is_an_error() {
+        let source_code = String::from("x = \"a${b");
+        let mut scanner = Scanner::new(source_code);
+        let errors = scanner.scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![ScanError::new(1, 5, "Unterminated string interpolation.")
+                .at(FileId::default(), Span::new(4, 9))]
+        );
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let mut map = SourceMap::new();
+        let file = map.add("spans.lox", "var ñu = \"é\";\n  ñu >= 1.5;");
+        let mut scanner = Scanner::with_file(map.file(file).source.clone(), file);
+        let tokens = scanner.scan_tokens().unwrap();
+
+        for token in &tokens {
+            assert_eq!(token.file, file);
+            assert_eq!(map.snippet(file, token.span), Some(token.lexeme.as_ref()));
+            assert_eq!(
+                map.line_col(file, token.span.start),
+                (token.line, token.col as usize)
+            );
+        }
+        assert_eq!(tokens[5].span, Span::new(18, 21));
+        assert_eq!((tokens[5].line, tokens[5].col), (2, 3));
+    }
+
+    /// Hands out its bytes one at a time, to split tokens and chars across reads.
+    struct Trickle(Vec<u8>, usize);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.1 == self.0.len() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[self.1];
+            self.1 += 1;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_eager_scan() {
+        let mut source = String::new();
+        for i in 0..500 {
+            source.push_str(&format!(
+                "var año{i} = \"ñ ${{{i} + 0x1F}}\"; /* 🦤\n */ print año{i} >= 1.5e3;\n"
+            ));
+        }
+        let eager = Scanner::new(source.clone()).scan_tokens().unwrap();
+        let streamed: Vec<Token> = Scanner::from_reader(Trickle(source.into_bytes(), 0))
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(streamed.len(), eager.len());
+        for (streamed, eager) in streamed.iter().zip(&eager) {
+            assert_eq!(format!("{:?}", streamed), format!("{:?}", eager));
+        }
+    }
+
+    #[test]
+    fn test_stream_always_ends_with_eof() {
+        let mut scanner = Scanner::from_reader(io::Cursor::new("a @"));
+        assert_eq!(scanner.next().unwrap().unwrap().ty, TokenType::Identifier);
+        assert_eq!(
+            scanner.next().unwrap().unwrap_err().message,
+            "Unexpected character '@'."
+        );
+        let eof = scanner.next().unwrap().unwrap();
+        assert_eq!((eof.ty, eof.span), (TokenType::Eof, Span::new(3, 3)));
+        assert!(scanner.next().is_none());
+
+        let tokens = Scanner::new(String::new()).scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].ty, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_keyword_table() {
+        for (keyword, token_type) in KEYWORDS {
+            assert_eq!(Scanner::keyword(keyword), Some(token_type));
+            assert_eq!(token_type.lexeme(), Some(keyword));
+        }
+        for text in [
+            "", "a", "andy", "clas", "While", "nill", "thus", "brake", "cont",
+        ] {
+            assert_eq!(Scanner::keyword(text), None);
+        }
+    }
+
+    #[test]
+    fn test_identifiers_are_interned() {
+        let mut scanner = Scanner::new(String::from("cuenta = cuenta + otra;"));
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let symbol = |token: &Token| match token.literal {
+            Some(Literal::Identifier(symbol)) => symbol,
+            _ => panic!("expected an identifier, got {:?}", token),
+        };
+        assert_eq!(symbol(&tokens[0]), symbol(&tokens[2]));
+        assert_ne!(symbol(&tokens[0]), symbol(&tokens[4]));
+        assert_eq!(symbol(&tokens[0]), Symbol::intern("cuenta"));
+        assert!(matches!(tokens[1].lexeme, Cow::Borrowed("=")));
     }
 }
@@ -0,0 +1,193 @@
+use std::fmt;
+
+/// Identifies a file loaded in a `SourceMap`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+/// A half-open range of byte offsets, `start..end`, into the source of a file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    /// Byte offset where each line begins. The first entry is always 0.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: String, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    /// 1-based line and column of a byte offset. Columns count chars, not bytes, and an offset
+    /// inside a multi-byte char counts as the start of that char.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let col = self.source[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// Text of a 1-based line, without its line terminator.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.source.len());
+        Some(self.source[start..end].trim_end_matches(['\n', '\r']))
+    }
+
+    pub fn snippet(&self, span: Span) -> Option<&str> {
+        self.source.get(span.start..span.end)
+    }
+}
+
+/// Every file loaded during a run, so offsets stored in tokens and errors can be turned back
+/// into names, lines, columns and source text.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        self.files.push(SourceFile::new(name.into(), source.into()));
+        FileId(self.files.len() as u32 - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn line_col(&self, id: FileId, offset: usize) -> (usize, usize) {
+        self.file(id).line_col(offset)
+    }
+
+    pub fn snippet(&self, id: FileId, span: Span) -> Option<&str> {
+        self.file(id).snippet(span)
+    }
+
+    /// Formats `message` as `name:line:col: message`, followed by the first line of `span`
+    /// with the spanned part underlined.
+    pub fn render(&self, id: FileId, span: Span, message: &str) -> String {
+        let file = self.file(id);
+        let (line, col) = file.line_col(span.start);
+        let text = file.line(line).unwrap_or_default();
+        let underline_len = file
+            .snippet(span)
+            .map(|s| s.lines().next().unwrap_or_default().chars().count())
+            .unwrap_or_default()
+            .max(1);
+        format!(
+            "{}:{}:{}: {}\n{}\n{}{}",
+            file.name,
+            line,
+            col,
+            message,
+            text,
+            " ".repeat(col - 1),
+            "^".repeat(underline_len)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col_across_files() {
+        let mut map = SourceMap::new();
+        let first = map.add("first.lox", "var a = 1;\nprint a;\n");
+        let second = map.add("second.lox", "// ñandú\nvar b;");
+
+        assert_eq!(map.line_col(first, 0), (1, 1));
+        assert_eq!(map.line_col(first, 17), (2, 7));
+        assert_eq!(map.line_col(second, 10), (1, 9));
+        assert_eq!(map.line_col(second, 15), (2, 5));
+        assert_eq!(map.file(second).name, "second.lox");
+    }
+
+    #[test]
+    fn test_snippets_and_lines() {
+        let mut map = SourceMap::new();
+        let id = map.add("test.lox", "var a = 1;\r\nprint a;");
+
+        assert_eq!(map.snippet(id, Span::new(4, 5)), Some("a"));
+        assert_eq!(map.file(id).line(1), Some("var a = 1;"));
+        assert_eq!(map.file(id).line(2), Some("print a;"));
+        assert_eq!(map.file(id).line(3), None);
+    }
+
+    #[test]
+    fn test_offsets_inside_a_char() {
+        let mut map = SourceMap::new();
+        let id = map.add("test.lox", "print \"ñandú\";\nvar é;");
+
+        // 'ñ' takes bytes 7 and 8, and 'é' bytes 21 and 22.
+        assert_eq!(map.line_col(id, 8), (1, 8));
+        assert_eq!(map.line_col(id, 9), (1, 9));
+        assert_eq!(map.line_col(id, 22), (2, 5));
+        assert_eq!(
+            map.render(id, Span::new(8, 14), "Bad."),
+            "test.lox:1:8: Bad.\nprint \"ñandú\";\n       ^"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut map = SourceMap::new();
+        let id = map.add("test.lox", "var a = 1;\nprint nope;");
+
+        assert_eq!(
+            map.render(id, Span::new(17, 21), "Undefined variable 'nope'."),
+            "test.lox:2:7: Undefined variable 'nope'.\nprint nope;\n      ^^^^"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned identifier. Every occurrence of the same name maps to the same `Symbol`, so
+/// names can be copied and compared as plain integers by the scanner, the AST and the runtime.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The process-wide symbol table. Interned names are leaked so `Symbol::as_str` can hand out
+/// `&'static str`s; the table only grows with the number of distinct names ever seen.
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let symbol = Symbol(interner.names.len() as u32);
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        interner.names[self.0 as usize]
+    }
+
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Symbol({:?})", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning_is_idempotent() {
+        let a = Symbol::intern("contador");
+        let b = Symbol::intern(&String::from("contador"));
+        let c = Symbol::intern("contadora");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.as_str(), "contador");
+        assert_eq!(c.to_string(), "contadora");
+    }
+}
@@ -1,12 +1,18 @@
+use std::borrow::Cow;
 use std::fmt;
 
-use crate::scanner::tokentype::{Literal, TokenType};
+use crate::scanner::{
+    source::{FileId, Span},
+    tokentype::{Literal, TokenType},
+};
 
 #[derive(Clone)]
 pub struct Token {
     pub ty: TokenType,
-    pub lexeme: String,
+    pub lexeme: Cow<'static, str>,
     pub literal: Option<Literal>,
+    pub file: FileId,
+    pub span: Span,
     pub line: usize,
     pub col: u32,
 }
@@ -14,8 +20,10 @@ pub struct Token {
 impl Token {
     pub fn new(
         ty: TokenType,
-        lexeme: String,
+        lexeme: Cow<'static, str>,
         literal: Option<Literal>,
+        file: FileId,
+        span: Span,
         line: usize,
         col: u32,
     ) -> Self {
@@ -23,6 +31,8 @@ impl Token {
             ty,
             lexeme,
             literal,
+            file,
+            span,
             line,
             col,
         }
@@ -33,8 +43,8 @@ impl fmt::Debug for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Token {{ ty: {:?}, lexeme: \"{}\", literal: {:?}, line: {:?}, col: {:?}}}",
-            self.ty, self.lexeme, self.literal, self.line, self.col
+            "Token {{ ty: {:?}, lexeme: \"{}\", literal: {:?}, span: {}, line: {:?}, col: {:?}}}",
+            self.ty, self.lexeme, self.literal, self.span, self.line, self.col
         )
     }
 }
@@ -1,4 +1,6 @@
 use strum_macros::Display;
+
+use super::symbol::Symbol;
 #[derive(Eq, PartialEq, Debug, Copy, Clone, Display)]
 pub enum TokenType {
     LeftParen,
@@ -12,6 +14,8 @@ pub enum TokenType {
     Minus,
     Plus,
     Semicolon,
+    Colon,
+    Question,
     Slash,
     Star,
     Bang,
@@ -22,15 +26,23 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    // `=>`, between the parameters and body of an arrow function.
+    Lambda,
 
     // Literals.
     Identifier,
     String,
     Number,
+    // Pieces of an interpolated string: `"a${` is the start, `}b${` a middle and `}c"` the end.
+    InterpolationStart,
+    InterpolationMiddle,
+    InterpolationEnd,
 
     // Keywords.
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
@@ -45,13 +57,70 @@ pub enum TokenType {
     True,
     Var,
     While,
-    Lambda,
     Eof,
 }
 
+impl TokenType {
+    /// The source text of tokens that are always spelled the same way.
+    pub fn lexeme(self) -> Option<&'static str> {
+        let lexeme = match self {
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Semicolon => ";",
+            TokenType::Colon => ":",
+            TokenType::Question => "?",
+            TokenType::Slash => "/",
+            TokenType::Star => "*",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::Lambda => "=>",
+            TokenType::And => "and",
+            TokenType::Break => "break",
+            TokenType::Class => "class",
+            TokenType::Continue => "continue",
+            TokenType::Else => "else",
+            TokenType::False => "false",
+            TokenType::Fun => "fun",
+            TokenType::For => "for",
+            TokenType::If => "if",
+            TokenType::Nil => "nil",
+            TokenType::Or => "or",
+            TokenType::Print => "print",
+            TokenType::Return => "return",
+            TokenType::Super => "super",
+            TokenType::This => "this",
+            TokenType::True => "true",
+            TokenType::Var => "var",
+            TokenType::While => "while",
+            TokenType::Eof => "",
+            TokenType::Identifier
+            | TokenType::String
+            | TokenType::Number
+            | TokenType::InterpolationStart
+            | TokenType::InterpolationMiddle
+            | TokenType::InterpolationEnd => return None,
+        };
+        Some(lexeme)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Literal {
-    Identifier(String),
+    Identifier(Symbol),
     Str(String),
     Number(f64),
 }
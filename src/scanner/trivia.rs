@@ -0,0 +1,189 @@
+use super::{source::Span, token::Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+
+/// Source text that is not part of any token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// A token with the trivia around it. Trailing trivia runs up to and including the end of the
+/// token's line; everything else before a token is its leading trivia.
+#[derive(Debug, Clone)]
+pub struct LosslessToken {
+    pub leading: Vec<Trivia>,
+    pub token: Token,
+    pub trailing: Vec<Trivia>,
+}
+
+impl LosslessToken {
+    pub fn write_to(&self, out: &mut String) {
+        for trivia in &self.leading {
+            out.push_str(&trivia.text);
+        }
+        out.push_str(&self.token.lexeme);
+        for trivia in &self.trailing {
+            out.push_str(&trivia.text);
+        }
+    }
+}
+
+/// Rebuilds the exact source text a lossless token stream was scanned from.
+pub fn to_source(tokens: &[LosslessToken]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        token.write_to(&mut out);
+    }
+    out
+}
+
+/// Splits the text between tokens into trivia and attaches it to them. `tokens` must be the
+/// successful scan of `source` and end with an `Eof` token, which takes the trivia left over at
+/// the end of the file.
+pub fn attach_trivia(source: &str, tokens: Vec<Token>) -> Vec<LosslessToken> {
+    let mut result: Vec<LosslessToken> = Vec::with_capacity(tokens.len());
+    let mut position = 0;
+
+    for token in tokens {
+        let mut gap = split_trivia(source, position, token.span.start);
+        position = token.span.end;
+
+        if let Some(previous) = result.last_mut() {
+            let same_line = gap
+                .iter()
+                .position(|trivia| trivia.kind == TriviaKind::Newline)
+                .map_or(gap.len(), |newline| newline + 1);
+            let leading = gap.split_off(same_line);
+            previous.trailing = gap;
+            gap = leading;
+        }
+
+        result.push(LosslessToken {
+            leading: gap,
+            token,
+            trailing: Vec::new(),
+        });
+    }
+    result
+}
+
+fn split_trivia(source: &str, start: usize, end: usize) -> Vec<Trivia> {
+    let mut trivia = Vec::new();
+    let mut position = start;
+
+    while position < end {
+        let rest = &source[position..end];
+        let (kind, len) = if rest.starts_with("//") {
+            (
+                TriviaKind::LineComment,
+                rest.find('\n').unwrap_or(rest.len()),
+            )
+        } else if rest.starts_with("/*") {
+            (TriviaKind::BlockComment, block_comment_len(rest))
+        } else if rest.starts_with("\r\n") {
+            (TriviaKind::Newline, 2)
+        } else if rest.starts_with('\n') {
+            (TriviaKind::Newline, 1)
+        } else {
+            (TriviaKind::Whitespace, whitespace_len(rest))
+        };
+
+        trivia.push(Trivia {
+            kind,
+            text: rest[..len].to_string(),
+            span: Span::new(position, position + len),
+        });
+        position += len;
+    }
+    trivia
+}
+
+/// Length of the run of whitespace at the start of `text`, stopping before a line break. Never
+/// zero, so a stray character can't stall the caller.
+fn whitespace_len(text: &str) -> usize {
+    let mut len = 0;
+    for c in text.chars() {
+        if c == '\n' || text[len..].starts_with("\r\n") || (len > 0 && !c.is_whitespace()) {
+            break;
+        }
+        len += c.len_utf8();
+    }
+    len.max(1)
+}
+
+/// Length of the nested block comment at the start of `text`, or all of it if unterminated.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scanner::Scanner, tokentype::TokenType};
+
+    #[test]
+    fn test_trivia_attachment() {
+        let mut scanner = Scanner::new(String::from("a = 1; // one\r\n  /* two */ b"));
+        let tokens = scanner.scan_lossless().unwrap();
+
+        let semicolon = &tokens[3];
+        assert_eq!(semicolon.token.ty, TokenType::Semicolon);
+        let kinds: Vec<TriviaKind> = semicolon.trailing.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TriviaKind::Whitespace,
+                TriviaKind::LineComment,
+                TriviaKind::Newline
+            ]
+        );
+
+        let b = &tokens[4];
+        let leading: Vec<&str> = b.leading.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(leading, vec!["  ", "/* two */", " "]);
+        assert_eq!(tokens[5].token.ty, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_round_trip_data_files() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/data/test");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read_to_string(&path).unwrap();
+            let mut scanner = Scanner::new(source.clone());
+            let tokens = scanner.scan_lossless().unwrap();
+
+            assert_eq!(
+                to_source(&tokens),
+                source,
+                "{} did not round-trip",
+                path.display()
+            );
+        }
+    }
+}